@@ -2,6 +2,1175 @@ pub mod utils;
 
 #[cfg(test)]
 mod tests {
+    use utils::aabb::AABB;
+    use std::sync::Arc;
+    use utils::box_hitable::BoxHitable;
+    use utils::bvh::{BVHNode, LinearBVH, TwoLevelBVH};
+    use utils::csg::{Csg, CsgOp};
+    use utils::gltf_scene::parse_gltf;
+    use utils::heightfield::Heightfield;
+    use utils::hitable::{FlipNormals, Hitable, HitableList, HitRecord, RotateY, Transform, Translate};
+    use utils::matrix::Mat4;
+    use utils::mesh::{MeshData, TriangleMesh};
+    use utils::obj::{parse_mtl, parse_obj};
+    use utils::ply::parse_ply;
+    use utils::pnm::parse_pnm;
+    use utils::quadric::{Cone, Cylinder, Disk, Paraboloid};
+    use utils::stl::{parse_stl, StlOptions};
+    use utils::material::{DummyMat, Lambertian, Material};
+    use utils::random::{drand48, seed};
+    use utils::ray::Ray;
+    use utils::rect::{XZRect, YZRect};
+    use utils::sdf::{Repeat, Sdf, SdfBox, SdfFn, SdfHitable, SdfRoundBox, SdfSphere, SdfTorus, SmoothUnion,
+                     Twist};
+    use utils::sphere::{MovingSphere, Sphere};
+    use utils::texture::{ConstantTexture, ImageTexture};
+    use utils::torus::{solve_polynomial, Torus};
+    use utils::triangle::Triangle;
+    use utils::vec3::{dot, unit_vector, Vec3};
+    use utils::volume::{ConstantMedium, Density, GridDensity, HeterogeneousMedium, PerlinDensity};
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a.clone() - b.clone()).len() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn random_vec3(scale: f32) -> Vec3 {
+        Vec3::new(drand48() - 0.5, drand48() - 0.5, drand48() - 0.5) * scale
+    }
+
+    fn random_spheres(n: usize) -> Vec<Box<Hitable>> {
+        (0..n)
+            .map(|_| {
+                Box::new(Sphere::new(random_vec3(20.), 0.1 + drand48(), Box::new(DummyMat::new()))) as Box<Hitable>
+            })
+            .collect()
+    }
+
     #[test]
     fn it_works() {}
+
+    #[test]
+    fn moving_sphere_bounding_box_covers_whole_motion() {
+        let s = MovingSphere::new(Vec3::new(0., 0., 0.), Vec3::new(1., 2., 3.), 0., 1., 0.5, Box::new(DummyMat::new()));
+        let mut b = AABB::default();
+        assert!(s.bounding_box(0., 1., &mut b));
+        assert_vec3_eq(b.clone().min(), Vec3::new(-0.5, -0.5, -0.5));
+        assert_vec3_eq(b.max(), Vec3::new(1.5, 2.5, 3.5));
+    }
+
+    #[test]
+    fn moving_sphere_bounding_box_uses_requested_interval() {
+        let s = MovingSphere::new(Vec3::new(0., 0., 0.), Vec3::new(2., 0., -2.), 0., 1., 1., Box::new(DummyMat::new()));
+        let mut b = AABB::default();
+        assert!(s.bounding_box(0.25, 0.5, &mut b));
+        assert_vec3_eq(b.clone().min(), Vec3::new(-0.5, -1., -2.));
+        assert_vec3_eq(b.max(), Vec3::new(2., 1., 0.5));
+    }
+
+    #[test]
+    fn moving_sphere_bounding_box_is_order_independent() {
+        let s = MovingSphere::new(Vec3::new(1., 1., 1.),
+                                  Vec3::new(-1., -1., -1.),
+                                  0.,
+                                  1.,
+                                  0.5,
+                                  Box::new(DummyMat::new()));
+        let mut b = AABB::default();
+        assert!(s.bounding_box(0., 1., &mut b));
+        assert_vec3_eq(b.clone().min(), Vec3::new(-1.5, -1.5, -1.5));
+        assert_vec3_eq(b.max(), Vec3::new(1.5, 1.5, 1.5));
+    }
+
+    #[test]
+    fn sphere_bounding_box_is_centered() {
+        let s = Sphere::new(Vec3::new(1., -2., 3.), 0.5, Box::new(DummyMat::new()));
+        let mut b = AABB::default();
+        assert!(s.bounding_box(0., 1., &mut b));
+        assert_vec3_eq(b.clone().min(), Vec3::new(0.5, -2.5, 2.5));
+        assert_vec3_eq(b.max(), Vec3::new(1.5, -1.5, 3.5));
+    }
+
+    #[test]
+    fn hitable_list_bounding_box_covers_every_element() {
+        let list = HitableList::new(vec![Box::new(Sphere::new(Vec3::new(0., 0., 0.), 1., Box::new(DummyMat::new()))),
+                                         Box::new(Sphere::new(Vec3::new(5., 0., 0.), 1., Box::new(DummyMat::new()))),
+                                         Box::new(Sphere::new(Vec3::new(0., -5., 2.), 1., Box::new(DummyMat::new())))]);
+        let mut b = AABB::default();
+        assert!(list.bounding_box(0., 1., &mut b));
+        assert_vec3_eq(b.clone().min(), Vec3::new(-1., -6., -1.));
+        assert_vec3_eq(b.max(), Vec3::new(6., 1., 3.));
+    }
+
+    #[test]
+    fn xz_and_yz_rects_hit_uv_and_bounds() {
+        let floor = XZRect::new(0., 2., 1., 3., 5., Box::new(DummyMat::new()));
+        let rec = first_hit(&floor, Vec3::new(1., 0., 2.5), Vec3::new(0., 1., 0.)).unwrap();
+        assert!((rec.t - 5.).abs() < 1e-6);
+        assert!((rec.u - 0.5).abs() < 1e-6 && (rec.v - 0.75).abs() < 1e-6);
+        assert_vec3_eq(rec.p, Vec3::new(1., 5., 2.5));
+        assert_vec3_eq(rec.normal, Vec3::new(0., 1., 0.));
+        assert!(first_hit(&floor, Vec3::new(3., 0., 2.), Vec3::new(0., 1., 0.)).is_none());
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        let up = Ray::new(&Vec3::new(1., 0., 2.), &Vec3::new(0., 1., 0.), 0.);
+        assert!(!floor.hit(&up, 0.001, 4., &mut rec));
+        let mut bbox = AABB::default();
+        assert!(floor.bounding_box(0., 1., &mut bbox));
+        assert_vec3_eq(bbox.clone().min(), Vec3::new(0., 4.9999, 1.));
+        assert_vec3_eq(bbox.clone().max(), Vec3::new(2., 5.0001, 3.));
+
+        let wall = YZRect::new(0., 2., 1., 3., -1., Box::new(DummyMat::new()));
+        let rec = first_hit(&wall, Vec3::new(2., 1.5, 1.5), Vec3::new(-1., 0., 0.)).unwrap();
+        assert!((rec.t - 3.).abs() < 1e-6);
+        assert!((rec.u - 0.75).abs() < 1e-6 && (rec.v - 0.25).abs() < 1e-6);
+        assert_vec3_eq(rec.normal, Vec3::new(1., 0., 0.));
+        assert!(first_hit(&wall, Vec3::new(2., 2.5, 1.5), Vec3::new(-1., 0., 0.)).is_none());
+        assert!(wall.bounding_box(0., 1., &mut bbox));
+        assert_vec3_eq(bbox.clone().min(), Vec3::new(-1.0001, 0., 1.));
+        assert_vec3_eq(bbox.max(), Vec3::new(-0.9999, 2., 3.));
+    }
+
+    #[test]
+    fn box_normals_face_outwards() {
+        let b = BoxHitable::new(Vec3::new(-1., -2., -3.), Vec3::new(1., 2., 3.), Box::new(DummyMat::new()));
+        for a in 0..3 {
+            for &sign in [-1., 1.].iter() {
+                let mut outward = Vec3::new(0., 0., 0.);
+                outward.e[a] = sign;
+                let origin = Vec3::new(0.1, 0.2, 0.3) + outward.clone() * 10.;
+                let rec = first_hit(&b, origin, outward.clone() * -1.).unwrap();
+                assert!((rec.p.e[a] - sign * (a + 1) as f32).abs() < 1e-5, "{:?}", rec.p);
+                assert_vec3_eq(rec.normal, outward);
+            }
+        }
+        let mut bbox = AABB::default();
+        assert!(b.bounding_box(0., 1., &mut bbox));
+        assert_vec3_eq(bbox.clone().min(), Vec3::new(-1., -2., -3.));
+        assert_vec3_eq(bbox.max(), Vec3::new(1., 2., 3.));
+    }
+
+    #[test]
+    fn flip_normals_keeps_everything_but_the_normal() {
+        let rect = XZRect::new(0., 2., 1., 3., 5., Box::new(DummyMat::new()));
+        let flipped = FlipNormals::new(Box::new(rect.clone()));
+        let (origin, up) = (Vec3::new(0.5, 0., 2.), Vec3::new(0., 1., 0.));
+        let plain = first_hit(&rect, origin.clone(), up.clone()).unwrap();
+        let rec = first_hit(&flipped, origin.clone(), up.clone()).unwrap();
+        assert_eq!((rec.t, rec.u, rec.v), (plain.t, plain.u, plain.v));
+        assert_vec3_eq(rec.p, plain.p);
+        assert_vec3_eq(rec.normal, Vec3::new(0., -1., 0.));
+        let r = Ray::new(&origin, &up, 0.);
+        assert_vec3_eq(flipped.crossings(&r, 0.001, f32::MAX)[0].normal.clone(), Vec3::new(0., -1., 0.));
+        let (mut a, mut b) = (AABB::default(), AABB::default());
+        assert!(rect.bounding_box(0., 1., &mut a) && flipped.bounding_box(0., 1., &mut b));
+        assert_vec3_eq(a.clone().min(), b.clone().min());
+        assert_vec3_eq(a.max(), b.max());
+    }
+
+    #[test]
+    fn translate_and_rotate_y_move_hits_and_bounds() {
+        let cube = || -> Box<Hitable> {
+            Box::new(BoxHitable::new(Vec3::new(-1., -1., -1.), Vec3::new(1., 1., 1.), Box::new(DummyMat::new())))
+        };
+        let moved = Translate::new(cube(), Vec3::new(5., 0., 0.));
+        let rec = first_hit(&moved, Vec3::new(10., 0.5, 0.), Vec3::new(-1., 0., 0.)).unwrap();
+        assert!((rec.t - 4.).abs() < 1e-5);
+        assert_vec3_eq(rec.p, Vec3::new(6., 0.5, 0.));
+        assert_vec3_eq(rec.normal, Vec3::new(1., 0., 0.));
+        let mut bbox = AABB::default();
+        assert!(moved.bounding_box(0., 1., &mut bbox));
+        assert_vec3_eq(bbox.clone().min(), Vec3::new(4., -1., -1.));
+        assert_vec3_eq(bbox.clone().max(), Vec3::new(6., 1., 1.));
+
+        // a quarter turn takes the long x extent of the bar to -z
+        let bar = Box::new(BoxHitable::new(Vec3::new(0., -1., -1.), Vec3::new(2., 1., 1.), Box::new(DummyMat::new())));
+        let turned = RotateY::new(bar, 90.);
+        let rec = first_hit(&turned, Vec3::new(0.5, 0., -10.), Vec3::new(0., 0., 1.)).unwrap();
+        assert_vec3_eq(rec.p, Vec3::new(0.5, 0., -2.));
+        assert_vec3_eq(rec.normal, Vec3::new(0., 0., -1.));
+        let rec = first_hit(&turned, Vec3::new(10., 0., -0.5), Vec3::new(-1., 0., 0.)).unwrap();
+        assert_vec3_eq(rec.p, Vec3::new(1., 0., -0.5));
+        assert_vec3_eq(rec.normal, Vec3::new(1., 0., 0.));
+        assert!(turned.bounding_box(0., 1., &mut bbox));
+        assert_vec3_eq(bbox.clone().min(), Vec3::new(-1., -1., -2.));
+        assert_vec3_eq(bbox.clone().max(), Vec3::new(1., 1., 0.));
+
+        let diagonal = 2f32.sqrt();
+        assert!(RotateY::new(cube(), 45.).bounding_box(0., 1., &mut bbox));
+        assert_vec3_eq(bbox.clone().min(), Vec3::new(-diagonal, -1., -diagonal));
+        assert_vec3_eq(bbox.max(), Vec3::new(diagonal, 1., diagonal));
+    }
+
+    #[test]
+    fn constant_medium_free_paths_are_exponential() {
+        seed(1);
+        let slab = BoxHitable::new(Vec3::new(0., -1., -1.), Vec3::new(10., 1., 1.), Box::new(DummyMat::new()));
+        let fog = ConstantMedium::new(Box::new(slab), 0.3, Box::new(ConstantTexture::new(Vec3::new(1., 1., 1.))));
+        // a direction of length 2 checks that distances, not ray parameters, are exponential
+        let r = Ray::new(&Vec3::new(-1., 0., 0.), &Vec3::new(2., 0., 0.), 0.);
+        let samples = 20000;
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        let mut paths = vec![];
+        for _ in 0..samples {
+            if fog.hit(&r, 0.001, f32::MAX, &mut rec) {
+                assert_eq!(rec.mat.name(), "isotropic");
+                paths.push(rec.p.x());
+            }
+        }
+        for &d in [1., 3., 10.].iter() {
+            let below = paths.iter().filter(|&&x| x < d).count() as f32 / samples as f32;
+            let expected = 1. - (-0.3 * d).exp();
+            assert!((below - expected).abs() < 0.01, "{} {} {}", d, below, expected);
+        }
+
+        let above = Ray::new(&Vec3::new(-1., 5., 0.), &Vec3::new(1., 0., 0.), 0.);
+        for _ in 0..100 {
+            assert!(!fog.hit(&above, 0.001, f32::MAX, &mut rec));
+        }
+    }
+
+    #[test]
+    fn grid_density_transmittance_matches_beer_lambert() {
+        seed(1);
+        let (min, max) = (Vec3::new(0., -1., -1.), Vec3::new(4., 1., 1.));
+        let medium = |data: Vec<f32>| {
+            let slab = BoxHitable::new(min.clone(), max.clone(), Box::new(DummyMat::new()));
+            HeterogeneousMedium::new(Box::new(slab),
+                                     Box::new(GridDensity::new(2, 2, 2, data, min.clone(), max.clone())),
+                                     Box::new(ConstantTexture::new(Vec3::new(1., 1., 1.))))
+        };
+        let r = Ray::new(&Vec3::new(-1., 0., 0.), &Vec3::new(1., 0., 0.), 0.);
+        let samples = 5000;
+        let mean = |m: &HeterogeneousMedium, t_max: f32| {
+            (0..samples).map(|_| m.transmittance(&r, 0.001, t_max)).sum::<f32>() / samples as f32
+        };
+
+        // constant 0.4 over 4 units, and over the first 2 of them
+        let constant = medium(vec![0.4; 8]);
+        assert!((mean(&constant, f32::MAX) - (-1.6f32).exp()).abs() < 0.02);
+        assert!((mean(&constant, 3.) - (-0.8f32).exp()).abs() < 0.02);
+        // delta tracking collides as often as a homogeneous medium of the same density
+        let fog = ConstantMedium::new(Box::new(BoxHitable::new(min.clone(), max.clone(), Box::new(DummyMat::new()))),
+                                      0.4,
+                                      Box::new(ConstantTexture::new(Vec3::new(1., 1., 1.))));
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        let hits = |m: &Hitable, rec: &mut HitRecord| {
+            (0..samples).filter(|_| m.hit(&r, 0.001, f32::MAX, rec)).count() as f32 / samples as f32
+        };
+        let expected = 1. - (-1.6f32).exp();
+        assert!((hits(&constant, &mut rec) - expected).abs() < 0.02);
+        assert!((hits(&fog, &mut rec) - expected).abs() < 0.02);
+
+        // rising linearly from 0 to 0.8 along x has the same optical depth of 1.6
+        let ramp = medium(vec![0., 0.8, 0., 0.8, 0., 0.8, 0., 0.8]);
+        assert!((mean(&ramp, f32::MAX) - (-1.6f32).exp()).abs() < 0.02);
+        assert!((mean(&ramp, 3.) - (-0.4f32).exp()).abs() < 0.02);
+    }
+
+    #[test]
+    fn perlin_density_is_bounded_and_repeatable() {
+        seed(7);
+        let smoke = PerlinDensity::new(4., 7, 0.6);
+        seed(7);
+        let again = PerlinDensity::new(4., 7, 0.6);
+        for _ in 0..1000 {
+            let p = random_vec3(5.);
+            let d = smoke.density(&p);
+            assert!((0. ..=smoke.max_density()).contains(&d));
+            assert_eq!(d, again.density(&p));
+        }
+    }
+
+    fn assert_matches_brute_force(accel: &Hitable, list: &HitableList, rays: usize) {
+        let mut hits = 0;
+        for _ in 0..rays {
+            let r = Ray::new(&random_vec3(40.), &random_vec3(1.), 0.);
+            let mut list_rec = HitRecord::new(Box::new(DummyMat::new()));
+            let mut accel_rec = HitRecord::new(Box::new(DummyMat::new()));
+            let list_hit = list.hit(&r, 0.001, std::f32::MAX, &mut list_rec);
+            let accel_hit = accel.hit(&r, 0.001, std::f32::MAX, &mut accel_rec);
+            assert_eq!(list_hit, accel_hit);
+            if list_hit {
+                hits += 1;
+                assert!((list_rec.t - accel_rec.t).abs() < 1e-4);
+                assert_vec3_eq(list_rec.p, accel_rec.p);
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        seed(1);
+        let spheres = random_spheres(300);
+        let list = HitableList::new(spheres.clone());
+        let bvh = BVHNode::new(&spheres, 0., 1.);
+        assert_matches_brute_force(&bvh, &list, 2000);
+    }
+
+    #[test]
+    fn sah_bvh_matches_brute_force() {
+        seed(1);
+        let spheres = random_spheres(300);
+        let list = HitableList::new(spheres.clone());
+        let bvh = BVHNode::new_sah(&spheres, 0., 1.);
+        assert_matches_brute_force(&bvh, &list, 2000);
+    }
+
+    #[test]
+    fn linear_bvh_matches_brute_force() {
+        seed(1);
+        let spheres = random_spheres(500);
+        let list = HitableList::new(spheres.clone());
+        let bvh = LinearBVH::new(spheres, 0., 1.);
+        assert_eq!(bvh.primitive_count(), 500);
+        assert_matches_brute_force(&bvh, &list, 2000);
+    }
+
+    #[test]
+    fn linear_bvh_parallel_build_matches_brute_force() {
+        seed(1);
+        let spheres = random_spheres(20000);
+        let list = HitableList::new(spheres.clone());
+        let bvh = LinearBVH::with_threads(spheres, 0., 1., 4);
+        assert_eq!(bvh.primitive_count(), 20000);
+        assert_matches_brute_force(&bvh, &list, 100);
+    }
+
+    #[test]
+    fn linear_bvh_refit_follows_moved_primitives() {
+        seed(1);
+        let spheres = random_spheres(500);
+        let mut bvh = LinearBVH::new(spheres, 0., 1.);
+        let mut moved: Vec<Box<Hitable>> = vec![];
+        for i in 0..500 {
+            let s = Sphere::new(random_vec3(20.), 0.1 + drand48(), Box::new(DummyMat::new()));
+            *bvh.primitive_mut(i) = Box::new(s.clone());
+            moved.push(Box::new(s));
+        }
+        bvh.refit(0., 1.);
+        assert_matches_brute_force(&bvh, &HitableList::new(moved), 2000);
+    }
+
+    #[test]
+    fn transform_inverse_bounds_and_normals() {
+        let m = Mat4::translate(&Vec3::new(1., 2., 3.)) * Mat4::rotate(&Vec3::new(1., 1., 0.), 30.) *
+                Mat4::scale(&Vec3::new(2., 0.5, 3.));
+        let product = m * m.inverse().unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1. } else { 0. };
+                assert!((product.m[i][j] - expected).abs() < 1e-5, "{:?}", product);
+            }
+        }
+        let cube: Arc<Hitable> = Arc::new(BoxHitable::new(Vec3::new(-1., -1., -1.),
+                                                          Vec3::new(1., 1., 1.),
+                                                          Box::new(DummyMat::new())));
+        assert!(Transform::new(cube.clone(), Mat4::scale(&Vec3::new(1., 0., 1.))).is_none());
+
+        let turned = Transform::new(cube, Mat4::rotate(&Vec3::new(0., 1., 0.), 45.)).unwrap();
+        let mut bbox = AABB::default();
+        assert!(turned.bounding_box(0., 1., &mut bbox));
+        let diagonal = 2f32.sqrt();
+        assert_vec3_eq(bbox.clone().min(), Vec3::new(-diagonal, -1., -diagonal));
+        assert_vec3_eq(bbox.max(), Vec3::new(diagonal, 1., diagonal));
+
+        // normals of a stretched sphere follow the inverse transpose, not the matrix itself
+        let sphere: Arc<Hitable> = Arc::new(Sphere::new(Vec3::new(0., 0., 0.), 1., Box::new(DummyMat::new())));
+        let ellipsoid = Transform::new(sphere, Mat4::scale(&Vec3::new(2., 1., 1.))).unwrap();
+        let y = 0.5f32.sqrt();
+        let rec = first_hit(&ellipsoid, Vec3::new(10., y, 0.), Vec3::new(-1., 0., 0.)).unwrap();
+        assert_vec3_eq(rec.p, Vec3::new(diagonal, y, 0.));
+        assert_vec3_eq(rec.normal, unit_vector(Vec3::new(1., 2., 0.)));
+    }
+
+    #[test]
+    fn two_level_bvh_matches_brute_force_instances() {
+        seed(1);
+        let tree = random_spheres(50);
+        let shared: Arc<Hitable> = Arc::new(HitableList::new(tree.clone()));
+        let blas = Arc::new(LinearBVH::new(tree.clone(), 0., 1.));
+        let mut instances = vec![];
+        let mut brute: Vec<Box<Hitable>> = vec![];
+        let mut flat: Vec<Box<Hitable>> = vec![];
+        for _ in 0..200 {
+            let m = Mat4::translate(&random_vec3(200.)) * Mat4::rotate(&Vec3::new(0., 1., 0.), 360. * drand48()) *
+                    Mat4::scale(&Vec3::new(0.5, 1., 0.5));
+            instances.push((0, m));
+            brute.push(Box::new(Transform::new(shared.clone(), m).unwrap()));
+            for s in tree.iter() {
+                flat.push(Box::new(Transform::new(Arc::from(s.clone()), m).unwrap()));
+            }
+        }
+        assert!(TwoLevelBVH::new(vec![blas.clone()], vec![(1, Mat4::identity())], 0., 1.).is_none());
+        let tlas = TwoLevelBVH::new(vec![blas.clone()], instances, 0., 1.).unwrap();
+        assert_eq!(tlas.instance_count(), 200);
+        let memory = tlas.memory_usage();
+        assert_eq!(memory.bottom_level, blas.memory_usage());
+        // one BVH over every instanced sphere stores each of them 200 times
+        let flat = LinearBVH::new(flat, 0., 1.);
+        assert_eq!(flat.primitive_count(), 200 * 50);
+        assert!(memory.top_level + memory.bottom_level < flat.memory_usage() / 10);
+        assert_matches_brute_force(&tlas, &HitableList::new(brute), 500);
+    }
+
+    #[test]
+    fn triangle_interpolates_uvs_and_normals() {
+        let n = Vec3::new(0., 0., 1.);
+        let tilted = Vec3::new(1., 0., 1.);
+        let tri = Triangle::with_attributes([Vec3::new(0., 0., 0.), Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.)],
+                                            Some([n.clone(), tilted, n]),
+                                            [(0., 0.), (1., 0.), (0., 1.)],
+                                            Box::new(DummyMat::new()));
+        let r = Ray::new(&Vec3::new(0.5, 0.25, 1.), &Vec3::new(0., 0., -1.), 0.);
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        assert!(tri.hit(&r, 0.001, std::f32::MAX, &mut rec));
+        assert!((rec.t - 1.).abs() < 1e-6);
+        assert!((rec.u - 0.5).abs() < 1e-6 && (rec.v - 0.25).abs() < 1e-6);
+        assert!(rec.normal.x() > 0. && (rec.normal.len() - 1.).abs() < 1e-5);
+
+        let miss = Ray::new(&Vec3::new(0.75, 0.75, 1.), &Vec3::new(0., 0., -1.), 0.);
+        assert!(!tri.hit(&miss, 0.001, std::f32::MAX, &mut rec));
+    }
+
+    #[test]
+    fn tiny_triangle_is_hit() {
+        // a 0.01 mm facet of a part modelled in millimetres and scaled to metres
+        let s = 0.00001;
+        let tri = Triangle::new(Vec3::new(0., 0., 0.),
+                                Vec3::new(s, 0., 0.),
+                                Vec3::new(0., s, 0.),
+                                Box::new(DummyMat::new()));
+        let r = Ray::new(&Vec3::new(0.25 * s, 0.25 * s, 1.), &Vec3::new(0., 0., -1.), 0.);
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        assert!(tri.hit(&r, 0.001, std::f32::MAX, &mut rec));
+        assert!((rec.t - 1.).abs() < 1e-6);
+
+        let grazing = Ray::new(&Vec3::new(-1., 0.25 * s, 0.), &Vec3::new(1., 0., 0.), 0.);
+        assert!(!tri.hit(&grazing, 0.001, std::f32::MAX, &mut rec));
+    }
+
+    #[test]
+    fn triangle_mesh_matches_separate_triangles() {
+        seed(1);
+        // bumpy grid so that triangles overlap along random rays
+        let n = 20;
+        let mut positions = vec![];
+        for j in 0..n + 1 {
+            for i in 0..n + 1 {
+                positions.push(Vec3::new(i as f32 - 10., drand48() * 4. - 2., j as f32 - 10.));
+            }
+        }
+        let mut indices = vec![];
+        for j in 0..n {
+            for i in 0..n {
+                let k = (j * (n + 1) + i) as u32;
+                indices.push([k, k + 1, k + n as u32 + 1]);
+                indices.push([k + 1, k + n as u32 + 2, k + n as u32 + 1]);
+            }
+        }
+        let data = MeshData::new(positions, indices);
+        let triangles: Vec<Box<Hitable>> = (0..data.indices.len())
+            .map(|i| {
+                let (a, b, c) = data.triangle(i);
+                Box::new(Triangle::new(a.clone(), b.clone(), c.clone(), Box::new(DummyMat::new()))) as Box<Hitable>
+            })
+            .collect();
+        let mesh = TriangleMesh::new(data, Box::new(DummyMat::new()));
+        assert_eq!(mesh.triangle_count(), 800);
+        assert_matches_brute_force(&mesh, &HitableList::new(triangles), 2000);
+    }
+
+    #[test]
+    fn empty_triangle_mesh_is_never_hit() {
+        let mesh = TriangleMesh::new(MeshData::default(), Box::new(DummyMat::new()));
+        assert_eq!(mesh.triangle_count(), 0);
+        assert!(first_hit(&mesh, Vec3::new(0., 0., -5.), Vec3::new(0., 0., 1.)).is_none());
+        let mut bbox = AABB::default();
+        assert!(!mesh.bounding_box(0., 1., &mut bbox));
+    }
+
+    const CUBE_MTL: &'static str = "newmtl red\nKd 0.8 0.1 0.1\n\nnewmtl lamp\nKe 4 4 4\n";
+    const CUBE_OBJ: &'static str = "
+mtllib cube.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vn 0 0 -1
+usemtl red
+f 1//1 4//1 3//1 2//1
+f -4 -3 -2 -1  # relative indices
+f 1 2 6 5
+usemtl lamp
+f 4 8 7 3
+";
+
+    #[test]
+    fn obj_groups_faces_by_material_and_triangulates() {
+        let meshes = parse_obj(CUBE_OBJ.as_bytes(), "cube.obj", |lib| {
+                assert_eq!(lib, "cube.mtl");
+                parse_mtl(CUBE_MTL.as_bytes(), lib)
+            })
+            .unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].triangle_count(), 6);
+        assert_eq!(meshes[1].triangle_count(), 2);
+        // normals are dropped because not every corner of the group has one
+        assert!(meshes[0].data().normals.is_empty());
+
+        let r = Ray::new(&Vec3::new(0.5, 2., 0.5), &Vec3::new(0., -1., 0.), 0.);
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        assert!(meshes[1].hit(&r, 0.001, std::f32::MAX, &mut rec));
+        assert_eq!(rec.mat.name(), "diffuselight");
+    }
+
+    #[test]
+    fn obj_errors_report_line_numbers() {
+        let err = parse_obj("v 0 0 0\nv 1 0 0\n\nf 1 2 3\n".as_bytes(), "bad.obj", |_| unreachable!())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "bad.obj:4: vertex index 3 out of range (2 defined)");
+        let err = parse_obj("v 0 zero 0\n".as_bytes(), "bad.obj", |_| unreachable!()).err().unwrap();
+        assert_eq!(err.to_string(), "bad.obj:1: invalid number 'zero'");
+        let err = parse_mtl("Kd 1 1 1\n".as_bytes(), "bad.mtl").err().unwrap();
+        assert_eq!(err.to_string(), "bad.mtl:1: 'Kd' before any 'newmtl'");
+    }
+
+    #[test]
+    fn ply_ascii_reads_colors_and_triangulates() {
+        let src = "ply
+format ascii 1.0
+comment quad with vertex colors
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+        let data = parse_ply(src.as_bytes(), "quad.ply").unwrap();
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert!(data.normals.is_empty() && data.uvs.is_empty());
+        assert_vec3_eq(data.colors[1].clone(), Vec3::new(0., 1., 0.));
+
+        let mesh = TriangleMesh::with_vertex_colors(data);
+        let r = Ray::new(&Vec3::new(1., 0., 1.), &Vec3::new(0., 0., -1.), 0.);
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        assert!(mesh.hit(&r, 0.001, std::f32::MAX, &mut rec));
+        assert_eq!(rec.mat.name(), "lambertian");
+
+        let err = parse_ply(src.replace("4 0 1 2 3", "3 0 1 7").as_bytes(), "quad.ply").err().unwrap();
+        assert_eq!(err.to_string(), "quad.ply:18: vertex index 7 out of range (4 vertices)");
+    }
+
+    #[test]
+    fn ply_binary_little_endian_reads_normals_and_uvs() {
+        let mut bytes = b"ply
+format binary_little_endian 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar uint vertex_indices
+end_header
+"
+            .to_vec();
+        let vertices = [[0f32, 0., 0., 0., 0., 1., 0., 0.],
+                        [1., 0., 0., 0., 0., 1., 1., 0.],
+                        [0., 1., 0., 0., 0., 1., 0., 1.]];
+        for v in vertices.iter() {
+            for f in v.iter() {
+                bytes.extend_from_slice(&f.to_le_bytes());
+            }
+        }
+        bytes.push(3);
+        for i in 0..3u32 {
+            bytes.extend_from_slice(&i.to_le_bytes());
+        }
+        let data = parse_ply(&bytes, "tri.ply").unwrap();
+        assert_eq!(data.indices, vec![[0, 1, 2]]);
+        assert_vec3_eq(data.positions[2].clone(), Vec3::new(0., 1., 0.));
+        assert_vec3_eq(data.normals[1].clone(), Vec3::new(0., 0., 1.));
+        assert_eq!(data.uvs[1], (1., 0.));
+
+        bytes.pop();
+        let err = parse_ply(&bytes, "tri.ply").err().unwrap();
+        assert_eq!(err.to_string(), "tri.ply:4: unexpected end of file");
+    }
+
+    // two facets of a unit square, sharing the diagonal
+    const SQUARE_STL: &'static str = "solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 10 0 0
+      vertex 10 10 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 10 10 0
+      vertex 0 10 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    #[test]
+    fn stl_ascii_welds_and_scales() {
+        let flat = parse_stl(SQUARE_STL.as_bytes(), "square.stl", &StlOptions::default()).unwrap();
+        assert_eq!(flat.positions.len(), 6);
+        assert!(flat.normals.is_empty());
+
+        let options = StlOptions {
+            scale: 0.1,
+            weld: Some(1e-4),
+        };
+        let welded = parse_stl(SQUARE_STL.as_bytes(), "square.stl", &options).unwrap();
+        assert_eq!(welded.positions.len(), 4);
+        assert_eq!(welded.indices.len(), 2);
+        assert_vec3_eq(welded.positions[2].clone(), Vec3::new(1., 1., 0.));
+        for n in welded.normals.iter() {
+            assert_vec3_eq(n.clone(), Vec3::new(0., 0., 1.));
+        }
+
+        let err = parse_stl(SQUARE_STL.replace("vertex 10 0 0", "vertex 10 0").as_bytes(),
+                            "square.stl",
+                            &StlOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "square.stl:5: 'vertex' needs 3 numbers");
+    }
+
+    #[test]
+    fn stl_weld_compares_distances() {
+        let options = StlOptions {
+            scale: 1.,
+            weld: Some(0.01),
+        };
+        let shifted = |a: &str, b: &str| {
+            let text = SQUARE_STL.replacen("vertex 10 10 0", a, 1).replace("vertex 10 10 0", b);
+            parse_stl(text.as_bytes(), "square.stl", &options).unwrap().positions.len()
+        };
+        // closer than the tolerance but on either side of a cell boundary
+        assert_eq!(shifted("vertex 10.004 10 0", "vertex 10.0055 10 0"), 4);
+        // in neighbouring cells but farther apart than the tolerance
+        assert_eq!(shifted("vertex 10.0 10 0", "vertex 10.015 10 0"), 5);
+    }
+
+    #[test]
+    fn stl_binary_with_solid_header() {
+        let mut bytes = b"solid but actually binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        for f in [0f32, 0., 1., 0., 0., 0., 1., 0., 0., 0., 1., 0.].iter() {
+            bytes.extend_from_slice(&f.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[0, 0]);
+        let data = parse_stl(&bytes, "tri.stl", &StlOptions::default()).unwrap();
+        assert_eq!(data.indices, vec![[0, 1, 2]]);
+        assert_vec3_eq(data.positions[2].clone(), Vec3::new(0., 1., 0.));
+
+        // welding the whole facet into one vertex leaves nothing to render
+        let weld = StlOptions {
+            scale: 1.,
+            weld: Some(10.),
+        };
+        assert!(parse_stl(&bytes, "tri.stl", &weld).is_err());
+
+        // a facet count far beyond the file is rejected before anything is allocated
+        let mut huge = vec![0u8; 80];
+        huge.extend_from_slice(&u32::max_value().to_le_bytes());
+        huge.extend_from_slice(&[0; 50]);
+        let e = parse_stl(&huge, "huge.stl", &StlOptions::default()).err().unwrap();
+        assert_eq!(e.to_string(), "huge.stl: unexpected end of file, header announces 4294967295 facets");
+    }
+
+    // one triangle, placed by two nodes, and a camera at z = 5 looking back at the origin
+    const TRIANGLE_GLTF: &'static str = r#"{
+  "asset": {"version": "2.0"},
+  "scene": 0,
+  "scenes": [{"nodes": [0, 1, 2]}],
+  "nodes": [
+    {"mesh": 0},
+    {"mesh": 0, "translation": [10, 0, 0], "scale": [2, 2, 2]},
+    {"camera": 0, "translation": [0, 0, 5]}
+  ],
+  "cameras": [{"type": "perspective", "perspective": {"yfov": 0.5, "znear": 0.1}}],
+  "materials": [{"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0}}],
+  "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1, "material": 0}]}],
+  "buffers": [{"byteLength": 44,
+               "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA="}],
+  "bufferViews": [{"buffer": 0, "byteLength": 36}, {"buffer": 0, "byteOffset": 36, "byteLength": 6}],
+  "accessors": [
+    {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0]},
+    {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+  ]
+}"#;
+
+    #[test]
+    fn lambertian_samples_texture_at_hit_uv() {
+        let m = Lambertian::new(Box::new(ImageTexture::new(vec![255, 0, 0, 0, 0, 255], 2, 1)));
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        rec.normal = Vec3::new(0., 1., 0.);
+        let r = Ray::new(&Vec3::new(0., 1., 0.), &Vec3::new(0., -1., 0.), 0.);
+        let mut attenuation = Vec3::new(0., 0., 0.);
+        let mut scattered = r.clone();
+        for &(u, ref expected) in [(0.25, Vec3::new(1., 0., 0.)), (0.75, Vec3::new(0., 0., 1.))].iter() {
+            rec.u = u;
+            assert!(m.scatter(&r, &rec, &mut attenuation, &mut scattered));
+            assert_vec3_eq(attenuation.clone(), expected.clone());
+        }
+    }
+
+    #[test]
+    fn gltf_nodes_and_camera() {
+        let scene = parse_gltf(TRIANGLE_GLTF.as_bytes(), "triangle.gltf", 2.).unwrap();
+        assert_eq!(scene.world.list.len(), 2);
+        assert_eq!(scene.cameras.len(), 1);
+        assert_vec3_eq(scene.cameras[0].origin.clone(), Vec3::new(0., 0., 5.));
+        assert_vec3_eq(scene.cameras[0].w.clone(), Vec3::new(0., 0., 1.));
+
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        let down = Vec3::new(0., 0., -1.);
+        let r = Ray::new(&Vec3::new(0.2, 0.2, 1.), &down, 0.);
+        assert!(scene.world.hit(&r, 0.001, f32::MAX, &mut rec));
+        assert!((rec.t - 1.).abs() < 1e-5);
+        // the second instance is scaled by 2, so it reaches x = 12 but not y = 2.5
+        let r = Ray::new(&Vec3::new(11.5, 0.2, 1.), &down, 0.);
+        assert!(scene.world.hit(&r, 0.001, f32::MAX, &mut rec));
+        let r = Ray::new(&Vec3::new(10.2, 2.5, 1.), &down, 0.);
+        assert!(!scene.world.hit(&r, 0.001, f32::MAX, &mut rec));
+
+        let err = parse_gltf(b"{", "broken.gltf", 2.).err().unwrap();
+        assert!(err.to_string().starts_with("broken.gltf: "));
+    }
+
+    #[test]
+    fn gltf_material_defaults() {
+        let material_of = |json: String| {
+            let scene = parse_gltf(json.as_bytes(), "triangle.gltf", 2.).unwrap();
+            let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+            let r = Ray::new(&Vec3::new(0.2, 0.2, 1.), &Vec3::new(0., 0., -1.), 0.);
+            assert!(scene.world.hit(&r, 0.001, f32::MAX, &mut rec));
+            rec.mat.name()
+        };
+        assert_eq!(material_of(TRIANGLE_GLTF.to_string()), "lambertian");
+        // a primitive without a material gets a diffuse default rather than the spec's metal
+        assert_eq!(material_of(TRIANGLE_GLTF.replace(r#", "material": 0"#, "")), "lambertian");
+        // a material that leaves metallicFactor at its default of 1 is a metal
+        let red = r#"{"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0}}"#;
+        let bare = TRIANGLE_GLTF.replace(red, "{}");
+        assert_eq!(material_of(bare), "metal");
+    }
+
+    fn first_hit(h: &Hitable, origin: Vec3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        if h.hit(&Ray::new(&origin, &direction, 0.), 0.001, f32::MAX, &mut rec) {
+            Some(rec)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn quadrics_sides_caps_and_sweep() {
+        let c = Vec3::new(1., 2., 3.);
+        let cylinder = Cylinder::new(c.clone(), 1., 2., 360., true, Box::new(DummyMat::new()));
+        let rec = first_hit(&cylinder, Vec3::new(-5., 3., 3.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!((rec.t - 5.).abs() < 1e-4);
+        assert_vec3_eq(rec.normal, Vec3::new(-1., 0., 0.));
+        assert!((rec.v - 0.5).abs() < 1e-4);
+        let rec = first_hit(&cylinder, Vec3::new(1.2, 10., 3.), Vec3::new(0., -1., 0.)).unwrap();
+        assert!((rec.p.y() - 4.).abs() < 1e-4);
+        assert_vec3_eq(rec.normal, Vec3::new(0., 1., 0.));
+
+        // a half cylinder only covers z >= center.z, so the ray enters through the back wall
+        let half = Cylinder::new(c.clone(), 1., 2., 180., false, Box::new(DummyMat::new()));
+        let rec = first_hit(&half, Vec3::new(1., 3., -5.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!((rec.p.z() - 4.).abs() < 1e-4);
+        assert_vec3_eq(rec.normal, Vec3::new(0., 0., 1.));
+        assert!(first_hit(&half, Vec3::new(1.5, 10., 2.5), Vec3::new(0., -1., 0.)).is_none());
+
+        let cone = Cone::new(c.clone(), 1., 1., 360., true, Box::new(DummyMat::new()));
+        let rec = first_hit(&cone, Vec3::new(-5., 2.5, 3.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!((rec.p.x() - 0.5).abs() < 1e-4);
+        let s = 0.5f32.sqrt();
+        assert_vec3_eq(rec.normal, Vec3::new(-s, s, 0.));
+        let rec = first_hit(&cone, Vec3::new(1.2, -5., 3.), Vec3::new(0., 1., 0.)).unwrap();
+        assert_vec3_eq(rec.normal, Vec3::new(0., -1., 0.));
+
+        let paraboloid = Paraboloid::new(c.clone(), 2., 4., 360., false, Box::new(DummyMat::new()));
+        let rec = first_hit(&paraboloid, Vec3::new(2., 10., 3.), Vec3::new(0., -1., 0.)).unwrap();
+        // x^2 = r^2 y / h, so the wall at x = 1 is at y = 1
+        assert!((rec.p.y() - 3.).abs() < 1e-4);
+        assert!((rec.v - 0.25).abs() < 1e-4);
+
+        let annulus = Disk::new(c.clone(), 2., 1., 360., Box::new(DummyMat::new()));
+        assert!(first_hit(&annulus, Vec3::new(1.5, 5., 3.), Vec3::new(0., -1., 0.)).is_none());
+        let rec = first_hit(&annulus, Vec3::new(2.5, 5., 3.), Vec3::new(0., -1., 0.)).unwrap();
+        assert!((rec.v - 0.5).abs() < 1e-4);
+        assert_vec3_eq(rec.normal, Vec3::new(0., 1., 0.));
+    }
+
+    #[test]
+    fn polynomial_close_roots() {
+        // (x - 1) (x - 1.0001) (x + 3) (x - 2)
+        let roots = solve_polynomial(&[1., -1.0001, -7., 13.0007, -6.0006], -10., 10.);
+        assert_eq!(roots.len(), 4);
+        for (&x, &expected) in roots.iter().zip([-3., 1., 1.0001, 2.].iter()) {
+            assert!((x - expected).abs() < 1e-9, "{:?}", roots);
+        }
+        assert_eq!(solve_polynomial(&[1., -1.0001, -7., 13.0007, -6.0006], 0., 1.5).len(), 2);
+        assert!(solve_polynomial(&[1., 0., 0., 0., 1.], -10., 10.).is_empty());
+
+        // double roots come back twice, so tangent rays cross a closed surface an even number of times
+        assert_eq!(solve_polynomial(&[1., -2., 1.], -10., 10.), vec![1., 1.]);
+        // (x - 1)^2 (x + 2) (x - 3)
+        let roots = solve_polynomial(&[1., -3., -3., 11., -6.], -10., 10.);
+        assert_eq!(roots.len() % 2, 0, "{:?}", roots);
+        assert!((roots[0] + 2.).abs() < 1e-9 && (roots[roots.len() - 1] - 3.).abs() < 1e-9, "{:?}", roots);
+        let torus = Torus::new(Vec3::new(0., 0., 0.), 2., 0.5, Box::new(DummyMat::new()));
+        for &y in [0.5, -0.5].iter() {
+            let r = Ray::new(&Vec3::new(-5., y, 0.), &Vec3::new(1., 0., 0.), 0.);
+            assert_eq!(torus.crossings(&r, 0.001, f32::MAX).len() % 2, 0);
+        }
+    }
+
+    #[test]
+    fn torus_grazing_rays() {
+        seed(1);
+        let torus = Torus::new(Vec3::new(0., 0., 0.), 2., 0.5, Box::new(DummyMat::new()));
+        let on_surface = |p: &Vec3| {
+            let ring = (p.x() * p.x() + p.z() * p.z()).sqrt();
+            ((ring - 2.) * (ring - 2.) + p.y() * p.y()).sqrt() - 0.5
+        };
+        let along_x = Vec3::new(1., 0., 0.);
+        for &eps in [1e-2f32, 1e-3, 1e-4].iter() {
+            // skimming the top of the tube, from close by and from far away
+            for &x in [-5f32, -1000.].iter() {
+                let rec = first_hit(&torus, Vec3::new(x, 0.5 - eps, 0.), along_x.clone()).unwrap();
+                assert!(on_surface(&rec.p).abs() < 1e-3, "eps {} x {}: {:?}", eps, x, rec.p.e);
+                assert!(rec.p.x() < -1.5 && rec.p.x() > -2.5);
+                assert!(rec.normal.y() > 0.9);
+            }
+            assert!(first_hit(&torus, Vec3::new(-5., 0.5 + eps, 0.), along_x.clone()).is_none());
+            // passing the outer equator just outside and just inside
+            assert!(first_hit(&torus, Vec3::new(2.5 + eps, 0., -5.), Vec3::new(0., 0., 1.)).is_none());
+            let rec = first_hit(&torus, Vec3::new(2.5 - eps, 0., -5.), Vec3::new(0., 0., 1.)).unwrap();
+            assert!(on_surface(&rec.p).abs() < 1e-3);
+        }
+        // straight through the hole
+        assert!(first_hit(&torus, Vec3::new(0., 5., 0.), Vec3::new(0., -1., 0.)).is_none());
+        // touching the inner equator from inside the tube leaves the far wall as the exit
+        let rec = first_hit(&torus, Vec3::new(1.5, 0., -1.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!(on_surface(&rec.p).abs() < 1e-3);
+
+        for _ in 0..1000 {
+            let origin = random_vec3(10.);
+            let target = random_vec3(3.);
+            if let Some(rec) = first_hit(&torus, origin.clone(), target - origin) {
+                assert!(on_surface(&rec.p).abs() < 1e-3, "{:?}", rec.p.e);
+            }
+        }
+    }
+
+    #[test]
+    fn csg_operations() {
+        let sphere = || -> Box<Hitable> {
+            Box::new(Sphere::new(Vec3::new(0., 0., 0.), 1., Box::new(DummyMat::new())))
+        };
+        let half_space = || -> Box<Hitable> {
+            Box::new(BoxHitable::new(Vec3::new(0., -2., -2.), Vec3::new(2., 2., 2.), Box::new(DummyMat::new())))
+        };
+        let from_right = Vec3::new(-1., 0., 0.);
+
+        // the cut face of the hemisphere looks towards the part that was removed
+        let hemisphere = Csg::new(CsgOp::Difference, sphere(), half_space());
+        let rec = first_hit(&hemisphere, Vec3::new(5., 0.3, 0.), from_right.clone()).unwrap();
+        assert!(rec.p.x().abs() < 1e-4);
+        assert_vec3_eq(rec.normal, Vec3::new(1., 0., 0.));
+        let r = Ray::new(&Vec3::new(5., 0., 0.), &from_right, 0.);
+        assert_eq!(hemisphere.crossings(&r, 0.001, f32::MAX).len(), 2);
+
+        let cap = Csg::new(CsgOp::Intersection, sphere(), half_space());
+        let rec = first_hit(&cap, Vec3::new(-5., 0.3, 0.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!(rec.p.x().abs() < 1e-4);
+        assert_vec3_eq(rec.normal, Vec3::new(-1., 0., 0.));
+        assert!(first_hit(&cap, Vec3::new(-0.5, 5., 0.), Vec3::new(0., -1., 0.)).is_none());
+        let mut bbox = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
+        assert!(cap.bounding_box(0., 1., &mut bbox));
+        assert_vec3_eq(bbox.min(), Vec3::new(0., -1., -1.));
+
+        // starting inside the union, the only crossing left is the far side of the box
+        let union = Csg::new(CsgOp::Union, sphere(), half_space());
+        let rec = first_hit(&union, Vec3::new(-0.5, 0., 0.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!((rec.p.x() - 2.).abs() < 1e-4);
+        let rec = first_hit(&union, Vec3::new(-5., 0., 0.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!((rec.p.x() + 1.).abs() < 1e-4);
+
+        // nested: a hemisphere with a vertical hole, the crossings forwarded through a transform
+        let cylinder = Cylinder::new(Vec3::new(0., -2., 0.), 0.2, 4., 360., true, Box::new(DummyMat::new()));
+        let hole = Transform::new(Arc::new(cylinder), Mat4::translate(&Vec3::new(-0.5, 0., 0.))).unwrap();
+        let drilled = Csg::new(CsgOp::Difference, Box::new(hemisphere.clone()), Box::new(hole));
+        let rec = first_hit(&drilled, Vec3::new(-5., 0., 0.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!((rec.p.x() + 1.).abs() < 1e-4);
+        let rec = first_hit(&drilled, Vec3::new(-0.8, 0., 0.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!((rec.p.x() + 0.7).abs() < 1e-4);
+        assert_vec3_eq(rec.normal, Vec3::new(1., 0., 0.));
+        assert!(first_hit(&drilled, Vec3::new(-0.5, 5., 0.), Vec3::new(0., -1., 0.)).is_none());
+        assert!(first_hit(&drilled, Vec3::new(-0.1, 5., 0.), Vec3::new(0., -1., 0.)).is_some());
+    }
+
+    #[test]
+    fn csg_with_paraboloid_parallel_to_axis() {
+        // a ray along the axis meets the paraboloid's side once, not as a repeated root
+        let paraboloid = Paraboloid::new(Vec3::new(0., -1., 0.), 2., 2., 360., true, Box::new(DummyMat::new()));
+        let up = Ray::new(&Vec3::new(0.5, -10., 0.5), &Vec3::new(0., 1., 0.), 0.);
+        let ts: Vec<f32> = paraboloid.crossings(&up, 0.001, f32::MAX).iter().map(|c| c.t).collect();
+        assert_eq!(ts.len(), 2, "{:?}", ts);
+        assert!((ts[0] - 9.25).abs() < 1e-4 && (ts[1] - 11.).abs() < 1e-4, "{:?}", ts);
+
+        let block = BoxHitable::new(Vec3::new(-3., -3., -3.), Vec3::new(3., 2., 3.), Box::new(DummyMat::new()));
+        let both = Csg::new(CsgOp::Intersection, Box::new(block), Box::new(paraboloid));
+        let rec = first_hit(&both, Vec3::new(0.5, -10., 0.5), Vec3::new(0., 1., 0.)).unwrap();
+        assert!((rec.t - 9.25).abs() < 1e-4, "{}", rec.t);
+    }
+
+    #[test]
+    fn hitable_list_crossings_merge_overlapping_children() {
+        let ball = |x: f32| -> Box<Hitable> {
+            Box::new(Sphere::new(Vec3::new(x, 0., 0.), 1., Box::new(DummyMat::new())))
+        };
+        let list = HitableList::new(vec![ball(4.), ball(5.5), ball(10.)]);
+        let r = Ray::new(&Vec3::new(0., 0., 0.), &Vec3::new(1., 0., 0.), 0.);
+        let ts: Vec<f32> = list.crossings(&r, 0.001, f32::MAX).iter().map(|c| c.t).collect();
+        assert_eq!(ts, vec![3., 6.5, 9., 11.]);
+        // starting inside the overlap leaves only the exit of the union
+        let ts: Vec<f32> = list.crossings(&r, 4.8, 8.).iter().map(|c| c.t).collect();
+        assert_eq!(ts, vec![6.5]);
+        let cut = Csg::new(CsgOp::Difference, Box::new(list), ball(5.));
+        let ts: Vec<f32> = cut.crossings(&r, 0.001, f32::MAX).iter().map(|c| c.t).collect();
+        assert_eq!(ts, vec![3., 4., 6., 6.5, 9., 11.]);
+    }
+
+    #[test]
+    fn in_plane_rays_do_not_cross_rects() {
+        let rect = XZRect::new(-1., 1., -1., 1., 0., Box::new(DummyMat::new()));
+        let along = Ray::new(&Vec3::new(-5., 0., 0.), &Vec3::new(1., 0., 0.), 0.);
+        assert!(first_hit(&rect, Vec3::new(-5., 0., 0.), Vec3::new(1., 0., 0.)).is_none());
+        assert!(rect.crossings(&along, 0.001, f32::MAX).is_empty());
+        let sphere = Sphere::new(Vec3::new(0., 0., 0.), 0.5, Box::new(DummyMat::new()));
+        let cut = Csg::new(CsgOp::Difference, Box::new(sphere), Box::new(rect));
+        assert_eq!(cut.crossings(&along, 0.001, f32::MAX).len(), 2);
+    }
+
+    #[test]
+    fn csg_far_operands_terminate() {
+        // a fixed step past each crossing rounds away this far out, the default must still stop
+        let plane = XZRect::new(-1., 1., -1., 1., 3000., Box::new(DummyMat::new()));
+        let up = Ray::new(&Vec3::new(0., 0., 0.), &Vec3::new(0., 1., 0.), 0.);
+        assert_eq!(plane.crossings(&up, 0.001, f32::MAX).len(), 1);
+
+        let far_box = Translate::new(Box::new(BoxHitable::new(Vec3::new(-1., -1., -1.),
+                                                              Vec3::new(1., 1., 1.),
+                                                              Box::new(DummyMat::new()))),
+                                     Vec3::new(3000., 0., 0.));
+        let sphere = Sphere::new(Vec3::new(0., 0., 0.), 1., Box::new(DummyMat::new()));
+        let carved = Csg::new(CsgOp::Difference, Box::new(far_box), Box::new(sphere));
+        let r = Ray::new(&Vec3::new(-5., 0., 0.), &Vec3::new(1., 0., 0.), 0.);
+        let crossings = carved.crossings(&r, 0.001, f32::MAX);
+        let ts: Vec<f32> = crossings.iter().map(|c| c.t).collect();
+        assert_eq!(ts.len(), 2, "{:?}", ts);
+        assert!((ts[0] - 3004.).abs() < 1e-2 && (ts[1] - 3006.).abs() < 1e-2, "{:?}", ts);
+        assert_vec3_eq(crossings[0].normal.clone(), Vec3::new(-1., 0., 0.));
+        assert_vec3_eq(crossings[1].normal.clone(), Vec3::new(1., 0., 0.));
+    }
+
+    #[test]
+    fn sdf_sphere_matches_analytic() {
+        seed(1);
+        let analytic = Sphere::new(Vec3::new(1., 2., 3.), 1.5, Box::new(DummyMat::new()));
+        let bounds = AABB::new(Vec3::new(-0.5, 0.5, 1.5), Vec3::new(2.5, 3.5, 4.5));
+        let marched = SdfHitable::new(Box::new(SdfSphere::new(Vec3::new(1., 2., 3.), 1.5)),
+                                      bounds,
+                                      Box::new(DummyMat::new()));
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = random_vec3(20.);
+            let direction = Vec3::new(1., 2., 3.) + random_vec3(3.) - origin.clone();
+            let expected = first_hit(&analytic, origin.clone(), direction.clone());
+            let found = first_hit(&marched, origin.clone(), direction.clone());
+            match (expected, found) {
+                (Some(e), Some(f)) => {
+                    hits += 1;
+                    // sphere tracing stops within a small distance of the surface, which a
+                    // grazing ray reaches well before the exact intersection
+                    assert!(((f.p.clone() - Vec3::new(1., 2., 3.)).len() - 1.5).abs() < 1e-3);
+                    if dot(&unit_vector(direction), &e.normal).abs() > 0.2 {
+                        assert!((e.p - f.p).len() < 1e-3);
+                        assert!((e.normal - f.normal).len() < 1e-2);
+                    }
+                }
+                (None, None) => {}
+                // rays grazing the silhouette may go either way
+                (e, f) => {
+                    let p = e.or(f).unwrap().p;
+                    assert!(dot(&unit_vector(direction), &unit_vector(p - Vec3::new(1., 2., 3.))).abs() < 0.05);
+                }
+            }
+        }
+        assert!(hits > 50);
+
+        // from inside, the exit is found
+        let rec = first_hit(&marched, Vec3::new(1., 2., 3.), Vec3::new(0., 2., 0.)).unwrap();
+        assert!((rec.p.y() - 3.5).abs() < 1e-3);
+        assert!((rec.t - 0.75).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sdf_operators() {
+        seed(1);
+        let blob = SmoothUnion::new(Box::new(SdfSphere::new(Vec3::new(-0.6, 0., 0.), 0.5)),
+                                    Box::new(SdfRoundBox::new(Vec3::new(0.6, 0., 0.), Vec3::new(0.5, 0.5, 0.5), 0.1)),
+                                    0.5);
+        // the blend fills the gap between the two shapes
+        assert!(blob.distance(&Vec3::new(0., 0., 0.)) < 0.);
+        assert!(SdfBox::new(Vec3::new(0., 0., 0.), Vec3::new(1., 2., 3.)).distance(&Vec3::new(0., 3., 0.)) - 1. < 1e-6);
+        let twisted = Twist::new(Box::new(SdfBox::new(Vec3::new(0., 0., 0.), Vec3::new(0.3, 1., 0.3))), 1.5, 0.5);
+        let rings = Repeat::new(Box::new(SdfTorus::new(Vec3::new(0., 0., 0.), 0.3, 0.1)), Vec3::new(1., 0., 1.));
+        let ground = SdfFn::new(|p: &Vec3| p.y() + 1., 1.);
+        let shapes: Vec<(Box<Sdf>, AABB)> =
+            vec![(Box::new(blob), AABB::new(Vec3::new(-1.5, -1., -1.), Vec3::new(1.5, 1., 1.))),
+                 (Box::new(twisted), AABB::new(Vec3::new(-0.5, -1., -0.5), Vec3::new(0.5, 1., 0.5))),
+                 (Box::new(rings), AABB::new(Vec3::new(-3., -0.1, -3.), Vec3::new(3., 0.1, 3.))),
+                 (Box::new(ground), AABB::new(Vec3::new(-10., -1.1, -10.), Vec3::new(10., -0.9, 10.)))];
+        for (sdf, bounds) in shapes.into_iter() {
+            let hitable = SdfHitable::new(sdf.clone(), bounds, Box::new(DummyMat::new()));
+            let mut hits = 0;
+            for _ in 0..200 {
+                let origin = Vec3::new(0., 5., 0.) + random_vec3(4.);
+                if let Some(rec) = first_hit(&hitable, origin.clone(), random_vec3(2.) - origin) {
+                    hits += 1;
+                    assert!(sdf.distance(&rec.p).abs() < 1e-3);
+                }
+            }
+            assert!(hits > 0);
+        }
+    }
+
+    #[test]
+    fn heightfield_matches_triangle_mesh() {
+        seed(1);
+        let (nx, nz) = (13, 9);
+        let heights: Vec<f32> = (0..nx * nz).map(|_| drand48()).collect();
+        let (min, max) = (Vec3::new(-3., -1., -2.), Vec3::new(3., 1., 2.));
+        let terrain = Heightfield::new(nx, nz, heights.clone(), min.clone(), max.clone(), Box::new(DummyMat::new()));
+
+        let mut mesh = MeshData::default();
+        for j in 0..nz {
+            for i in 0..nx {
+                mesh.positions.push(Vec3::new(-3. + 6. * i as f32 / (nx - 1) as f32,
+                                              -1. + 2. * heights[j * nx + i],
+                                              -2. + 4. * j as f32 / (nz - 1) as f32));
+            }
+        }
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                let (a, b, c, d) = (j * nx + i, (j + 1) * nx + i, (j + 1) * nx + i + 1, j * nx + i + 1);
+                mesh.indices.push([a as u32, b as u32, c as u32]);
+                mesh.indices.push([a as u32, c as u32, d as u32]);
+            }
+        }
+        let reference = TriangleMesh::new(mesh, Box::new(DummyMat::new()));
+
+        let mut hits = 0;
+        for n in 0..2000 {
+            // include rays straight down and rays along the grid axes
+            let origin = random_vec3(12.);
+            let direction = match n % 4 {
+                0 => Vec3::new(0., -1., 0.),
+                1 => Vec3::new(1., 0., 0.),
+                _ => random_vec3(4.) - origin.clone(),
+            };
+            let expected = first_hit(&reference, origin.clone(), direction.clone());
+            let found = first_hit(&terrain, origin, direction);
+            match (expected, found) {
+                (Some(e), Some(f)) => {
+                    hits += 1;
+                    assert!((e.t - f.t).abs() < 1e-4, "{} {}", e.t, f.t);
+                    assert!(f.normal.y() > 0.);
+                    assert!(f.u >= 0. && f.u <= 1. && f.v >= 0. && f.v <= 1.);
+                }
+                (None, None) => {}
+                (e, f) => panic!("mesh {:?} heightfield {:?}", e.map(|r| r.t), f.map(|r| r.t)),
+            }
+        }
+        assert!(hits > 100);
+
+        let perlin = Heightfield::from_perlin(32, 32, 0.5, 4, min, max, Box::new(DummyMat::new()));
+        assert!(first_hit(&perlin, Vec3::new(0.1, 5., 0.2), Vec3::new(0., -1., 0.)).is_some());
+    }
+
+    #[test]
+    fn pnm_ascii_and_binary() {
+        let ascii = parse_pnm(b"P2\n# heights\n3 2\n4\n0 1 2\n3 4 4\n", "grid.pgm").unwrap();
+        assert_eq!((ascii.width, ascii.height), (3, 2));
+        assert_eq!(ascii.pixels, vec![0., 0.25, 0.5, 0.75, 1., 1.]);
+
+        let mut binary = b"P5 2 1 65535\n".to_vec();
+        binary.extend_from_slice(&[0xff, 0xff, 0x80, 0x00]);
+        let image = parse_pnm(&binary, "deep.pgm").unwrap();
+        assert_eq!(image.pixels[0], 1.);
+        assert!((image.pixels[1] - 0.5).abs() < 1e-4);
+
+        let color = parse_pnm(b"P6 1 1 255\n\xff\xff\xff", "white.ppm").unwrap();
+        assert!((color.pixels[0] - 1.).abs() < 1e-6);
+
+        let err = parse_pnm(b"P2\n2 1\n255\n0\nx\n", "bad.pgm").err().unwrap();
+        assert_eq!(err.to_string(), "bad.pgm:5: invalid sample 'x'");
+        let err = parse_pnm(b"P5 2 2 255\n\x00", "short.pgm").err().unwrap();
+        assert_eq!(err.to_string(), "short.pgm: raster has 1 bytes, expected 4");
+        let err = parse_pnm(b"P5 4294967296 4294967296 255\n\x00", "huge.pgm").err().unwrap();
+        assert_eq!(err.to_string(), "huge.pgm: image size 4294967296x4294967296 is too large");
+
+        // a single pixel is stretched into a flat terrain at its height
+        let min = Vec3::new(-1., 0., -1.);
+        let max = Vec3::new(1., 2., 1.);
+        let pixel = parse_pnm(b"P2 1 1 4 2", "pixel.pgm").unwrap();
+        let flat = Heightfield::from_image(&pixel, min, max, Box::new(DummyMat::new()));
+        let rec = first_hit(&flat, Vec3::new(0.3, 5., -0.2), Vec3::new(0., -1., 0.)).unwrap();
+        assert!((rec.p.y() - 1.).abs() < 1e-5);
+
+        // terrain with no height range at all is still hit and has a box with some thickness
+        let level = Heightfield::new(3, 3, vec![0.; 9], Vec3::new(-1., 0.5, -1.), Vec3::new(1., 0.5, 1.),
+                                     Box::new(DummyMat::new()));
+        let rec = first_hit(&level, Vec3::new(0.3, 5., -0.2), Vec3::new(0.1, -1., 0.)).unwrap();
+        assert!((rec.p.y() - 0.5).abs() < 1e-5);
+        assert_vec3_eq(rec.normal, Vec3::new(0., 1., 0.));
+        let mut bbox = AABB::default();
+        assert!(level.bounding_box(0., 1., &mut bbox));
+        assert!(bbox.clone().max().y() > bbox.min().y());
+    }
 }
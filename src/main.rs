@@ -12,6 +12,7 @@ use utils::camera::Camera;
 use utils::material::{DummyMat, Lambertian, Metal, Dielectric};
 use utils::texture::{CheckerTexture, ConstantTexture};
 use utils::random::drand48;
+use utils::bvh::LinearBVH;
 
 const CONCURRENCY: i32 = 8;
 const NX: i32 = 1200;
@@ -108,7 +109,7 @@ fn main() {
     let ns = NS;
     println!("P3\n{} {}\n255", nx, ny);
 
-    let world: HitableList = random_scene();
+    let world = HitableList::new(vec![Box::new(LinearBVH::new(random_scene().list, 0., 1.))]);
 
     let lookfrom = Vec3::new(13., 2., 3.);
     let lookat = Vec3::new(0., 0., 0.);
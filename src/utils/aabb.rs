@@ -21,13 +21,16 @@ impl AABB {
     pub fn max(self) -> Vec3 {
         self.max
     }
-}
 
-impl Hitable for AABB {
-    fn box_clone(&self) -> Box<Hitable> {
-        Box::new((*self).clone())
+    pub fn surface_area(&self) -> f32 {
+        let dx = self.max.x() - self.min.x();
+        let dy = self.max.y() - self.min.y();
+        let dz = self.max.z() - self.min.z();
+        2. * (dx * dy + dy * dz + dz * dx)
     }
-    fn hit(&self, r: &Ray, tmin: f32, tmax: f32, _rec: &mut HitRecord) -> bool {
+
+    /// the part of `(tmin, tmax)` in which the ray is inside the box
+    pub fn clip(&self, r: &Ray, mut tmin: f32, mut tmax: f32) -> Option<(f32, f32)> {
         for a in 0..3 {
             // let t0 = ffmin((self.min.e[a] - r.origin().e[a]) / r.direction().e[a],
             //               (self.max.e[a] - r.origin().e[a]) / r.direction().e[a]);
@@ -37,28 +40,37 @@ impl Hitable for AABB {
             // let tmax = ffmax(t1, tmax);
 
             let inv_d = 1. / r.direction().e[a];
-            let mut t0 = (self.clone().min().e[a] - r.origin().e[a]) * inv_d;
-            let mut t1 = (self.clone().max().e[a] - r.origin().e[a]) * inv_d;
+            let mut t0 = (self.min.e[a] - r.origin().e[a]) * inv_d;
+            let mut t1 = (self.max.e[a] - r.origin().e[a]) * inv_d;
             if inv_d < 0. {
                 mem::swap(&mut t0, &mut t1);
             }
-            let tmin = if t0 > tmin {
+            tmin = if t0 > tmin {
                 t0
             } else {
                 tmin
             };
-            let tmax = if t1 < tmax {
+            tmax = if t1 < tmax {
                 t1
             } else {
                 tmax
             };
 
             if tmax <= tmin {
-                return false;
+                return None;
             }
         }
 
-        true
+        Some((tmin, tmax))
+    }
+}
+
+impl Hitable for AABB {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, tmin: f32, tmax: f32, _rec: &mut HitRecord) -> bool {
+        self.clip(r, tmin, tmax).is_some()
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32, _vox: &mut AABB) -> bool {
@@ -0,0 +1,88 @@
+use std::f32;
+use std::mem;
+use utils::hitable::{FlipNormals, Hitable, HitableList, HitRecord};
+use utils::vec3::Vec3;
+use utils::ray::Ray;
+use utils::material::Material;
+use utils::aabb::AABB;
+use utils::rect::{XYRect, XZRect, YZRect};
+
+#[derive(Clone)]
+pub struct BoxHitable {
+    pmin: Vec3,
+    pmax: Vec3,
+    list: HitableList,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl BoxHitable {
+    pub fn new(p0: Vec3, p1: Vec3, mt: Box<Material>) -> Self {
+        let list: Vec<Box<Hitable>> = vec![
+            Box::new(XYRect::new(p0.x(), p1.x(), p0.y(), p1.y(), p1.z(), mt.clone())),
+            Box::new(FlipNormals::new(Box::new(XYRect::new(p0.x(), p1.x(), p0.y(), p1.y(), p0.z(), mt.clone())))),
+            Box::new(XZRect::new(p0.x(), p1.x(), p0.z(), p1.z(), p1.y(), mt.clone())),
+            Box::new(FlipNormals::new(Box::new(XZRect::new(p0.x(), p1.x(), p0.z(), p1.z(), p0.y(), mt.clone())))),
+            Box::new(YZRect::new(p0.y(), p1.y(), p0.z(), p1.z(), p1.x(), mt.clone())),
+            Box::new(FlipNormals::new(Box::new(YZRect::new(p0.y(), p1.y(), p0.z(), p1.z(), p0.x(), mt.clone())))),
+        ];
+        Self {
+            pmin: p0,
+            pmax: p1,
+            list: HitableList::new(list),
+            mat: mt,
+        }
+    }
+}
+
+impl Hitable for BoxHitable {
+    fn hit(&self, r: &Ray, t0: f32, t1: f32, rec: &mut HitRecord) -> bool {
+        self.list.hit(r, t0, t1, rec)
+    }
+    /// slab test for the entry and exit, so that a ray through an edge is not counted twice
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let mut enter = (-f32::MAX, 0);
+        let mut exit = (f32::MAX, 0);
+        for a in 0..3 {
+            let inv_d = 1. / r.direction().e[a];
+            let mut t0 = (self.pmin.e[a] - r.origin().e[a]) * inv_d;
+            let mut t1 = (self.pmax.e[a] - r.origin().e[a]) * inv_d;
+            if inv_d < 0. {
+                mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > enter.0 {
+                enter = (t0, a);
+            }
+            if t1 < exit.0 {
+                exit = (t1, a);
+            }
+        }
+        if enter.0 > exit.0 {
+            return vec![];
+        }
+        [enter, exit]
+            .iter()
+            .filter(|&&(t, _)| t > t_min && t < t_max)
+            .map(|&(t, a)| {
+                let mut rec = HitRecord::new(self.mat.clone());
+                rec.t = t;
+                rec.p = r.point_at_parameter(t);
+                // the same parametrization as the rectangle on that face
+                let (b, c) = if a == 2 { (0, 1) } else if a == 1 { (0, 2) } else { (1, 2) };
+                rec.u = (rec.p.e[b] - self.pmin.e[b]) / (self.pmax.e[b] - self.pmin.e[b]);
+                rec.v = (rec.p.e[c] - self.pmin.e[c]) / (self.pmax.e[c] - self.pmin.e[c]);
+                let mut n = Vec3::new(0., 0., 0.);
+                n.e[a] = if rec.p.e[a] - self.pmin.e[a] < self.pmax.e[a] - rec.p.e[a] { -1. } else { 1. };
+                rec.normal = n;
+                rec
+            })
+            .collect()
+    }
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = AABB::new(self.pmin.clone(), self.pmax.clone());
+        true
+    }
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+}
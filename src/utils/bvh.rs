@@ -1,17 +1,23 @@
 use std::cmp::Ordering;
+use std::mem;
+use std::sync::Arc;
+use std::thread;
 use utils::aabb::{AABB, surrounding_box};
-use utils::hitable::{Hitable, HitRecord};
+use utils::hitable::{Hitable, HitRecord, Transform};
 use utils::random::drand48;
 use utils::ray::Ray;
+use utils::matrix::Mat4;
 use utils::vec3::Vec3;
 
+const SAH_BINS: usize = 12;
+
 macro_rules! box_compare {
     ( $i:ident, $a:ident, $b:ident ) => {
         {
     let mut box_left = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
     let mut box_right = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
     if !$a.bounding_box(0., 0., &mut box_left) || !$b.bounding_box(0., 0., &mut box_right) {
-        // error
+        panic!("no bounding box in BVHNode constructor");
     }
     let c = box_left.min().$i() - box_right.min().$i();
     if c < 0. {
@@ -26,7 +32,7 @@ macro_rules! box_compare {
 }
 
 #[derive(Clone)]
-struct BVHNode {
+pub struct BVHNode {
     left: Box<Hitable>,
     right: Box<Hitable>,
     vox: AABB,
@@ -34,7 +40,15 @@ struct BVHNode {
 
 #[allow(dead_code)]
 impl BVHNode {
-    pub fn new(l: &mut Vec<Box<Hitable>>, time0: f32, time1: f32) -> Self {
+    /// median split on a random axis. the tree is built on the calling thread, only
+    /// `LinearBVH` and `IndexBVH` spread their builds over several threads.
+    pub fn new(l: &[Box<Hitable>], time0: f32, time1: f32) -> Self {
+        assert!(!l.is_empty(), "BVHNode needs at least one hitable");
+        // clone the list once, sub-lists are moved down the recursion
+        Self::build_median(l.to_vec(), time0, time1)
+    }
+
+    fn build_median(mut l: Vec<Box<Hitable>>, time0: f32, time1: f32) -> Self {
         let axis = (3. * drand48()) as i32;
         if axis == 0 {
             l.sort_by(|a, b| box_compare!(x, a, b));
@@ -44,22 +58,71 @@ impl BVHNode {
             l.sort_by(|a, b| box_compare!(z, a, b));
         }
         let (left, right): (Box<Hitable>, Box<Hitable>) = if l.len() == 1 {
-            (l[0].clone(), l[0].clone())
+            (l[0].clone(), l.pop().unwrap())
         } else if l.len() == 2 {
-            (l[0].clone(), l[1].clone())
+            let right = l.pop().unwrap();
+            (l.pop().unwrap(), right)
         } else {
-            let (svf, svl) = l.split_at(l.len() / 2);
-            let mut vl: Vec<Box<Hitable>> = vec![];
-            let mut vf: Vec<Box<Hitable>> = vec![];
-            vf.extend(svf.iter().map(|ref i| (**i).clone()));
-            vl.extend(svl.iter().map(|ref i| (**i).clone()));
-            (Box::new(BVHNode::new(&mut vf, time0, time1)), Box::new(BVHNode::new(&mut vl, time0, time1)))
+            let vl = l.split_off(l.len() / 2);
+            (Box::new(BVHNode::build_median(l, time0, time1)), Box::new(BVHNode::build_median(vl, time0, time1)))
         };
+        Self::from_children(left, right, time0, time1)
+    }
+
+    /// builds the tree with a binned surface area heuristic instead of a random-axis median split.
+    /// slower to build, but gives much tighter trees for clustered scenes.
+    pub fn new_sah(l: &[Box<Hitable>], time0: f32, time1: f32) -> Self {
+        assert!(!l.is_empty(), "BVHNode needs at least one hitable");
+        Self::build_sah(l.to_vec(), time0, time1)
+    }
+
+    fn build_sah(mut l: Vec<Box<Hitable>>, time0: f32, time1: f32) -> Self {
+        if l.len() <= 2 {
+            let right = l.pop().unwrap();
+            let left = l.pop().unwrap_or_else(|| right.clone());
+            return Self::from_children(left, right, time0, time1);
+        }
+
+        let info: Vec<PrimInfo> = l.iter()
+            .enumerate()
+            .map(|(i, h)| {
+                let b = primitive_bounds(h, time0, time1);
+                PrimInfo {
+                    bounds: b,
+                    centroid: b.centroid(),
+                    index: i,
+                }
+            })
+            .collect();
+        let bins = Binning::new(&info);
+
+        let (mut vf, mut vl): (Vec<Box<Hitable>>, Vec<Box<Hitable>>) = (vec![], vec![]);
+        match bins.best_split(&info) {
+            Some((axis, split)) => {
+                for (h, p) in l.into_iter().zip(info.iter()) {
+                    if bins.bin(p, axis) < split {
+                        vf.push(h);
+                    } else {
+                        vl.push(h);
+                    }
+                }
+            }
+            None => {
+                // every centroid coincides, nothing to gain from SAH
+                vl = l.split_off(l.len() / 2);
+                vf = l;
+            }
+        }
+        let left: Box<Hitable> = Box::new(BVHNode::build_sah(vf, time0, time1));
+        let right: Box<Hitable> = Box::new(BVHNode::build_sah(vl, time0, time1));
+        Self::from_children(left, right, time0, time1)
+    }
 
+    fn from_children(left: Box<Hitable>, right: Box<Hitable>, time0: f32, time1: f32) -> Self {
         let mut box_left = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
         let mut box_right = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
         if !left.bounding_box(time0, time1, &mut box_left) || !right.bounding_box(time0, time1, &mut box_right) {
-            // error
+            panic!("no bounding box in BVHNode constructor");
         }
         Self {
             left: left,
@@ -78,7 +141,7 @@ impl Hitable for BVHNode {
         if self.vox.hit(r, t_min, t_max, &mut temp_rec) {
             let (mut left_rec, mut right_rec) = (HitRecord::new(rec.mat.clone()), HitRecord::new(rec.mat.clone()));
             let hit_left = self.left.hit(r, t_min, t_max, &mut left_rec);
-            let hit_right = self.left.hit(r, t_min, t_max, &mut right_rec);
+            let hit_right = self.right.hit(r, t_min, t_max, &mut right_rec);
             if hit_left && hit_right {
                 *rec = if left_rec.t < right_rec.t {
                     left_rec
@@ -102,3 +165,537 @@ impl Hitable for BVHNode {
         true
     }
 }
+
+const MAX_LEAF_PRIMS: usize = 4;
+// past this depth splits fall back to the median, which bounds the traversal stack
+const MAX_SAH_DEPTH: usize = 32;
+const TRAVERSAL_STACK: usize = 64;
+// subtrees smaller than this are not worth a thread
+const PARALLEL_BUILD_MIN: usize = 4096;
+
+/// plain-array box used by `LinearBVH`, `AABB` keeps its coordinates on the heap
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl Bounds {
+    fn empty() -> Self {
+        Self {
+            min: [f32::MAX; 3],
+            max: [-f32::MAX; 3],
+        }
+    }
+
+    fn from_aabb(b: &AABB) -> Self {
+        let (min, max) = (b.clone().min(), b.clone().max());
+        Self {
+            min: [min.x(), min.y(), min.z()],
+            max: [max.x(), max.y(), max.z()],
+        }
+    }
+
+    fn to_aabb(self) -> AABB {
+        AABB::new(Vec3::new(self.min[0], self.min[1], self.min[2]),
+                  Vec3::new(self.max[0], self.max[1], self.max[2]))
+    }
+
+    fn union(&self, o: &Bounds) -> Bounds {
+        let mut r = *self;
+        for a in 0..3 {
+            r.min[a] = r.min[a].min(o.min[a]);
+            r.max[a] = r.max[a].max(o.max[a]);
+        }
+        r
+    }
+
+    fn centroid(&self) -> [f32; 3] {
+        [0.5 * (self.min[0] + self.max[0]), 0.5 * (self.min[1] + self.max[1]), 0.5 * (self.min[2] + self.max[2])]
+    }
+
+    fn surface_area(&self) -> f32 {
+        if self.min[0] > self.max[0] {
+            return 0.;
+        }
+        let d = [self.max[0] - self.min[0], self.max[1] - self.min[1], self.max[2] - self.min[2]];
+        2. * (d[0] * d[1] + d[1] * d[2] + d[2] * d[0])
+    }
+
+    fn hit(&self, origin: &[f32; 3], inv_d: &[f32; 3], mut tmin: f32, mut tmax: f32) -> bool {
+        for a in 0..3 {
+            let mut t0 = (self.min[a] - origin[a]) * inv_d[a];
+            let mut t1 = (self.max[a] - origin[a]) * inv_d[a];
+            if inv_d[a] < 0. {
+                mem::swap(&mut t0, &mut t1);
+            }
+            tmin = tmin.max(t0);
+            tmax = tmax.min(t1);
+            if tmax < tmin {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// interior nodes keep their second child at `offset` (the first one follows them directly),
+/// leaves keep `count` primitives starting at `offset`.
+#[derive(Clone, Copy, Debug)]
+struct LinearNode {
+    bounds: Bounds,
+    offset: u32,
+    count: u16,
+    axis: u8,
+}
+
+#[derive(Clone, Copy)]
+struct PrimInfo {
+    bounds: Bounds,
+    centroid: [f32; 3],
+    index: usize,
+}
+
+/// centroid bounds of a set of primitives, split into `SAH_BINS` bins along each axis
+struct Binning {
+    cmin: [f32; 3],
+    extent: [f32; 3],
+}
+
+impl Binning {
+    fn new(info: &[PrimInfo]) -> Self {
+        let mut cmin = [f32::MAX; 3];
+        let mut cmax = [-f32::MAX; 3];
+        for p in info.iter() {
+            for a in 0..3 {
+                cmin[a] = cmin[a].min(p.centroid[a]);
+                cmax[a] = cmax[a].max(p.centroid[a]);
+            }
+        }
+        Self {
+            cmin: cmin,
+            extent: [cmax[0] - cmin[0], cmax[1] - cmin[1], cmax[2] - cmin[2]],
+        }
+    }
+
+    fn bin(&self, p: &PrimInfo, axis: usize) -> usize {
+        let b = ((p.centroid[axis] - self.cmin[axis]) / self.extent[axis] * SAH_BINS as f32) as usize;
+        b.min(SAH_BINS - 1)
+    }
+
+    fn widest(&self) -> usize {
+        let e = self.extent;
+        if e[0] >= e[1] && e[0] >= e[2] {
+            0
+        } else if e[1] >= e[2] {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// cheapest (axis, split bin) by the surface area heuristic, primitives in bins below the
+    /// split go left. `None` when no axis separates the centroids.
+    fn best_split(&self, info: &[PrimInfo]) -> Option<(usize, usize)> {
+        // (cost, axis, split bin)
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in 0..3 {
+            if self.extent[axis] <= 0. {
+                continue;
+            }
+            let mut counts = [0usize; SAH_BINS];
+            let mut bins = [Bounds::empty(); SAH_BINS];
+            for p in info.iter() {
+                let b = self.bin(p, axis);
+                counts[b] += 1;
+                bins[b] = bins[b].union(&p.bounds);
+            }
+            // sweep from the right once, then from the left while evaluating every split
+            let mut right_area = [0f32; SAH_BINS];
+            let mut right_count = [0usize; SAH_BINS];
+            let (mut acc, mut n) = (Bounds::empty(), 0);
+            for i in (1..SAH_BINS).rev() {
+                acc = acc.union(&bins[i]);
+                n += counts[i];
+                right_area[i] = acc.surface_area();
+                right_count[i] = n;
+            }
+            let (mut acc, mut n) = (Bounds::empty(), 0);
+            for split in 1..SAH_BINS {
+                acc = acc.union(&bins[split - 1]);
+                n += counts[split - 1];
+                if n == 0 || right_count[split] == 0 {
+                    continue;
+                }
+                let cost = acc.surface_area() * n as f32 + right_area[split] * right_count[split] as f32;
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+        best.map(|(_, axis, split)| (axis, split))
+    }
+}
+
+/// BVH flattened into a depth-first array of nodes. primitives are owned by the BVH and
+/// reordered so that every leaf references a contiguous range of them. traversal is iterative,
+/// visits the nearer child first and does not allocate. the top levels are built in parallel,
+/// with the same builder as `IndexBVH`.
+#[derive(Clone)]
+pub struct LinearBVH {
+    nodes: Vec<LinearNode>,
+    primitives: Vec<Box<Hitable>>,
+    // position in `primitives` of each hitable, in the order they were given to `new`
+    slot_of: Vec<u32>,
+}
+
+#[allow(dead_code)]
+impl LinearBVH {
+    pub fn new(l: Vec<Box<Hitable>>, time0: f32, time1: f32) -> Self {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Self::with_threads(l, time0, time1, threads)
+    }
+
+    /// like `new`, but spreads the top levels of the build over at most `threads` threads
+    pub fn with_threads(l: Vec<Box<Hitable>>, time0: f32, time1: f32, threads: usize) -> Self {
+        assert!(!l.is_empty(), "LinearBVH needs at least one hitable");
+        let bounds = l.iter().map(|h| primitive_bounds(h, time0, time1)).collect();
+        let (nodes, order) = build_nodes(bounds, threads);
+
+        let mut slots: Vec<Option<Box<Hitable>>> = l.into_iter().map(Some).collect();
+        let primitives = order.iter().map(|&i| slots[i as usize].take().unwrap()).collect();
+        let mut slot_of = vec![0u32; order.len()];
+        for (slot, &i) in order.iter().enumerate() {
+            slot_of[i as usize] = slot as u32;
+        }
+        Self {
+            nodes: nodes,
+            primitives: primitives,
+            slot_of: slot_of,
+        }
+    }
+
+    /// the `i`-th hitable given to `new`. after moving primitives call `refit`.
+    pub fn primitive_mut(&mut self, i: usize) -> &mut Box<Hitable> {
+        &mut self.primitives[self.slot_of[i] as usize]
+    }
+
+    /// recomputes every node's bounds bottom-up while keeping the tree topology.
+    /// much cheaper than a rebuild when primitives only move a little between frames,
+    /// although the tree degrades if they move far from where it was built.
+    pub fn refit(&mut self, time0: f32, time1: f32) {
+        // children are always stored after their parent, so a reverse sweep sees them first
+        for i in (0..self.nodes.len()).rev() {
+            let node = self.nodes[i];
+            let offset = node.offset as usize;
+            self.nodes[i].bounds = if node.count > 0 {
+                self.primitives[offset..offset + node.count as usize]
+                    .iter()
+                    .fold(Bounds::empty(), |b, h| b.union(&primitive_bounds(h, time0, time1)))
+            } else {
+                self.nodes[i + 1].bounds.union(&self.nodes[offset].bounds)
+            };
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn primitive_count(&self) -> usize {
+        self.primitives.len()
+    }
+
+    /// approximate bytes held by this BVH: its nodes, index tables and the primitives themselves.
+    /// heap data owned by the primitives (materials, textures, shared meshes) is not followed.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.nodes.capacity() * mem::size_of::<LinearNode>() +
+        self.slot_of.capacity() * mem::size_of::<u32>() +
+        self.primitives.capacity() * mem::size_of::<Box<Hitable>>() +
+        self.primitives.iter().map(|h| mem::size_of_val(&**h)).sum::<usize>()
+    }
+}
+
+/// builds flattened nodes over `bounds`. leaves refer to ranges of the returned order,
+/// which maps back to indices into `bounds`.
+fn build_nodes(bounds: Vec<Bounds>, threads: usize) -> (Vec<LinearNode>, Vec<u32>) {
+    let mut info: Vec<PrimInfo> = bounds.into_iter()
+        .enumerate()
+        .map(|(i, b)| {
+            PrimInfo {
+                bounds: b,
+                centroid: b.centroid(),
+                index: i,
+            }
+        })
+        .collect();
+    let mut nodes = Vec::with_capacity(2 * info.len() / MAX_LEAF_PRIMS + 1);
+    build_linear(&mut info, 0, 0, parallel_depth(threads), &mut nodes);
+    (nodes, info.iter().map(|p| p.index as u32).collect())
+}
+
+fn primitive_bounds(h: &Box<Hitable>, time0: f32, time1: f32) -> Bounds {
+    let mut b = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
+    if !h.bounding_box(time0, time1, &mut b) {
+        panic!("no bounding box in BVH constructor");
+    }
+    Bounds::from_aabb(&b)
+}
+
+/// number of tree levels whose subtrees are built on their own thread, enough to occupy every core
+fn parallel_depth(threads: usize) -> usize {
+    (usize::BITS - threads.saturating_sub(1).leading_zeros()) as usize
+}
+
+fn build_linear(info: &mut [PrimInfo],
+                first: usize,
+                depth: usize,
+                par_depth: usize,
+                nodes: &mut Vec<LinearNode>)
+                -> usize {
+    let bounds = info.iter().fold(Bounds::empty(), |b, p| b.union(&p.bounds));
+    let idx = nodes.len();
+    nodes.push(LinearNode {
+        bounds: bounds,
+        offset: first as u32,
+        count: 0,
+        axis: 0,
+    });
+    if info.len() <= MAX_LEAF_PRIMS {
+        nodes[idx].count = info.len() as u16;
+        return idx;
+    }
+
+    let bins = Binning::new(info);
+    let best = if depth < MAX_SAH_DEPTH {
+        bins.best_split(info)
+    } else {
+        None
+    };
+    let (axis, mut mid) = match best {
+        Some((axis, split)) => {
+            let mut i = 0;
+            for j in 0..info.len() {
+                if bins.bin(&info[j], axis) < split {
+                    info.swap(i, j);
+                    i += 1;
+                }
+            }
+            (axis, i)
+        }
+        None => (bins.widest(), 0),
+    };
+    let info_len = info.len();
+    if mid == 0 || mid == info_len {
+        info.sort_by(|a, b| a.centroid[axis].partial_cmp(&b.centroid[axis]).unwrap_or(Ordering::Equal));
+        mid = info.len() / 2;
+    }
+
+    let (left, right) = info.split_at_mut(mid);
+    let second = if par_depth > 0 && info_len >= PARALLEL_BUILD_MIN {
+        // build the second subtree into its own buffer on another thread, then
+        // append it and relocate its interior child indices
+        let mut right_nodes = vec![];
+        thread::scope(|s| {
+            let handle = s.spawn(|| {
+                let mut v = Vec::with_capacity(2 * right.len() / MAX_LEAF_PRIMS + 1);
+                build_linear(right, first + mid, depth + 1, par_depth - 1, &mut v);
+                v
+            });
+            build_linear(left, first, depth + 1, par_depth - 1, nodes);
+            right_nodes = handle.join().unwrap();
+        });
+        let base = nodes.len();
+        nodes.extend(right_nodes.into_iter().map(|mut n| {
+            if n.count == 0 {
+                n.offset += base as u32;
+            }
+            n
+        }));
+        base
+    } else {
+        build_linear(left, first, depth + 1, par_depth, nodes);
+        build_linear(right, first + mid, depth + 1, par_depth, nodes)
+    };
+    nodes[idx].offset = second as u32;
+    nodes[idx].axis = axis as u8;
+    idx
+}
+
+/// iterative near-to-far traversal of a flattened BVH. `leaf` is handed the primitive range of
+/// every leaf whose box the ray reaches before `closest`, and returns the new closest `t` if it hit.
+fn traverse<F>(nodes: &[LinearNode], r: &Ray, t_min: f32, t_max: f32, mut leaf: F) -> bool
+    where F: FnMut(usize, usize, f32) -> Option<f32>
+{
+    let origin = [r.origin().x(), r.origin().y(), r.origin().z()];
+    let inv_d = [1. / r.direction().x(), 1. / r.direction().y(), 1. / r.direction().z()];
+    let mut stack = [0usize; TRAVERSAL_STACK];
+    let mut sp = 0;
+    let mut current = 0;
+    let mut closest_so_far = t_max;
+    let mut hit_anything = false;
+    loop {
+        let node = &nodes[current];
+        if node.bounds.hit(&origin, &inv_d, t_min, closest_so_far) {
+            let offset = node.offset as usize;
+            if node.count > 0 {
+                if let Some(t) = leaf(offset, node.count as usize, closest_so_far) {
+                    hit_anything = true;
+                    closest_so_far = t;
+                }
+            } else {
+                let (near, far) = if inv_d[node.axis as usize] < 0. {
+                    (offset, current + 1)
+                } else {
+                    (current + 1, offset)
+                };
+                stack[sp] = far;
+                sp += 1;
+                current = near;
+                continue;
+            }
+        }
+        if sp == 0 {
+            break;
+        }
+        sp -= 1;
+        current = stack[sp];
+    }
+    hit_anything
+}
+
+impl Hitable for LinearBVH {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        traverse(&self.nodes, r, t_min, t_max, |offset, count, closest_so_far| {
+            let mut closest = None;
+            for h in self.primitives[offset..offset + count].iter() {
+                if h.hit(r, t_min, closest.unwrap_or(closest_so_far), rec) {
+                    closest = Some(rec.t);
+                }
+            }
+            closest
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, b: &mut AABB) -> bool {
+        *b = self.nodes[0].bounds.to_aabb();
+        true
+    }
+}
+
+/// flattened BVH over primitives that are owned elsewhere and referred to by index,
+/// e.g. the triangles of a mesh. a BVH over no primitives is empty and never hit.
+#[derive(Clone)]
+pub struct IndexBVH {
+    nodes: Vec<LinearNode>,
+    order: Vec<u32>,
+}
+
+#[allow(dead_code)]
+impl IndexBVH {
+    pub fn new(bounds: &[AABB]) -> Self {
+        if bounds.is_empty() {
+            return Self {
+                nodes: vec![],
+                order: vec![],
+            };
+        }
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let (nodes, order) = build_nodes(bounds.iter().map(Bounds::from_aabb).collect(), threads);
+        Self {
+            nodes: nodes,
+            order: order,
+        }
+    }
+
+    /// calls `hit(i, closest)` for every primitive `i` the ray may reach; it returns the
+    /// primitive's `t` when hit closer than `closest`. returns whether anything was hit.
+    pub fn traverse<F>(&self, r: &Ray, t_min: f32, t_max: f32, mut hit: F) -> bool
+        where F: FnMut(usize, f32) -> Option<f32>
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        traverse(&self.nodes, r, t_min, t_max, |offset, count, closest_so_far| {
+            let mut closest = None;
+            for &i in self.order[offset..offset + count].iter() {
+                if let Some(t) = hit(i as usize, closest.unwrap_or(closest_so_far)) {
+                    closest = Some(t);
+                }
+            }
+            closest
+        })
+    }
+
+    /// `None` when there are no primitives
+    pub fn bounds(&self) -> Option<AABB> {
+        self.nodes.first().map(|n| n.bounds.to_aabb())
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.nodes.capacity() * mem::size_of::<LinearNode>() +
+        self.order.capacity() * mem::size_of::<u32>()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BVHMemory {
+    pub top_level: usize,
+    pub bottom_level: usize,
+}
+
+/// two-level acceleration structure: a top-level BVH over transformed instances, each of which
+/// references one of a few shared bottom-level BVHs. geometry is stored once per bottom level
+/// no matter how many times it is instanced.
+#[derive(Clone)]
+pub struct TwoLevelBVH {
+    bottom: Vec<Arc<LinearBVH>>,
+    top: LinearBVH,
+}
+
+#[allow(dead_code)]
+impl TwoLevelBVH {
+    /// `instances` pairs an index into `bottom` with the object-to-world matrix of that instance.
+    /// returns `None` if an index is out of range or one of the matrices is not invertible.
+    pub fn new(bottom: Vec<Arc<LinearBVH>>, instances: Vec<(usize, Mat4)>, time0: f32, time1: f32) -> Option<Self> {
+        let list = instances.into_iter()
+            .map(|(i, m)| {
+                let blas: Arc<Hitable> = bottom.get(i)?.clone();
+                Transform::new(blas, m).map(|t| Box::new(t) as Box<Hitable>)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            top: LinearBVH::new(list, time0, time1),
+            bottom: bottom,
+        })
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.top.primitive_count()
+    }
+
+    pub fn memory_usage(&self) -> BVHMemory {
+        BVHMemory {
+            // `top` is part of `Self`, its own report counts the struct again
+            top_level: mem::size_of::<Self>() - mem::size_of::<LinearBVH>() + self.top.memory_usage() +
+                       self.bottom.capacity() * mem::size_of::<Arc<LinearBVH>>(),
+            bottom_level: self.bottom.iter().map(|b| b.memory_usage()).sum(),
+        }
+    }
+}
+
+impl Hitable for TwoLevelBVH {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        self.top.hit(r, t_min, t_max, rec)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, b: &mut AABB) -> bool {
+        self.top.bounding_box(t0, t1, b)
+    }
+}
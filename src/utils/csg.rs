@@ -0,0 +1,128 @@
+use std::f32;
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::Vec3;
+use utils::ray::Ray;
+use utils::aabb::{AABB, surrounding_box};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match *self {
+            CsgOp::Union => in_left || in_right,
+            CsgOp::Intersection => in_left && in_right,
+            CsgOp::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// boolean combination of two closed hitables. the crossings of both operands along the
+/// ray are merged, and the ones where the combined solid is entered or left are kept.
+/// surfaces the right operand cuts out of the left one of a difference face inwards.
+#[derive(Clone)]
+pub struct Csg {
+    op: CsgOp,
+    left: Box<Hitable>,
+    right: Box<Hitable>,
+}
+
+#[allow(dead_code)]
+impl Csg {
+    pub fn new(op: CsgOp, left: Box<Hitable>, right: Box<Hitable>) -> Self {
+        Self {
+            op: op,
+            left: left,
+            right: right,
+        }
+    }
+}
+
+impl Hitable for Csg {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        match self.crossings(r, t_min, t_max).into_iter().next() {
+            Some(c) => {
+                *rec = c;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        // an odd number of crossings ahead means the ray starts inside a closed operand
+        let left = self.left.crossings(r, t_min, f32::MAX);
+        let right = self.right.crossings(r, t_min, f32::MAX);
+        let mut in_left = left.len() % 2 == 1;
+        let mut in_right = right.len() % 2 == 1;
+        let mut inside = self.op.inside(in_left, in_right);
+        let mut crossings = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() || j < right.len() {
+            let from_left = j == right.len() || (i < left.len() && left[i].t <= right[j].t);
+            let c = if from_left {
+                in_left = !in_left;
+                i += 1;
+                &left[i - 1]
+            } else {
+                in_right = !in_right;
+                j += 1;
+                &right[j - 1]
+            };
+            if c.t >= t_max {
+                break;
+            }
+            let now_inside = self.op.inside(in_left, in_right);
+            if now_inside != inside {
+                inside = now_inside;
+                let mut rec = c.clone();
+                if !from_left && self.op == CsgOp::Difference {
+                    rec.normal = rec.normal * -1.;
+                }
+                crossings.push(rec);
+            }
+        }
+        crossings
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
+        let mut left = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
+        let mut right = left.clone();
+        if !self.left.bounding_box(t0, t1, &mut left) {
+            return false;
+        }
+        match self.op {
+            CsgOp::Difference => *vox = left,
+            CsgOp::Union => {
+                if !self.right.bounding_box(t0, t1, &mut right) {
+                    return false;
+                }
+                *vox = surrounding_box(left, right);
+            }
+            CsgOp::Intersection => {
+                // an unbounded right operand cannot grow the left one
+                if !self.right.bounding_box(t0, t1, &mut right) {
+                    *vox = left;
+                    return true;
+                }
+                let (lmin, lmax) = (left.clone().min(), left.max());
+                let (rmin, rmax) = (right.clone().min(), right.max());
+                let mut min = Vec3::new(0., 0., 0.);
+                let mut max = Vec3::new(0., 0., 0.);
+                for a in 0..3 {
+                    min.e[a] = lmin.e[a].max(rmin.e[a]);
+                    max.e[a] = lmax.e[a].min(rmax.e[a]).max(min.e[a]);
+                }
+                *vox = AABB::new(min, max);
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,298 @@
+extern crate gltf;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use self::gltf::camera::Projection;
+use self::gltf::image::{Data as ImageData, Format};
+use self::gltf::material::AlphaMode;
+use self::gltf::mesh::Mode;
+use self::gltf::{buffer, Document, Node};
+use utils::vec3::Vec3;
+use utils::camera::Camera;
+use utils::hitable::{Hitable, HitableList, Transform};
+use utils::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use utils::texture::{ConstantTexture, ImageTexture, Texture};
+use utils::matrix::Mat4;
+use utils::mesh::{MeshData, TriangleMesh};
+use utils::load::LoadError;
+
+/// objects and cameras of the default scene of a glTF asset
+pub struct GltfScene {
+    pub world: HitableList,
+    pub cameras: Vec<Camera>,
+}
+
+struct Importer<'a> {
+    file: &'a str,
+    buffers: &'a [buffer::Data],
+    images: &'a [ImageData],
+    default_aspect: f32,
+    meshes: HashMap<(usize, usize), Arc<Hitable>>,
+    materials: HashMap<Option<usize>, Box<Material>>,
+    world: Vec<Box<Hitable>>,
+    cameras: Vec<Camera>,
+}
+
+/// converts any 8, 16 or 32 bit image to RGB8, multiplying by the base color factor
+fn to_rgb8(image: &ImageData, factor: &[f32; 4]) -> Option<Vec<u8>> {
+    let (channels, bytes) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let p = &image.pixels;
+    let read = |o: usize| match bytes {
+        1 => p[o] as f32 / 255.,
+        2 => u16::from_ne_bytes([p[o], p[o + 1]]) as f32 / 65535.,
+        _ => f32::from_ne_bytes([p[o], p[o + 1], p[o + 2], p[o + 3]]),
+    };
+    let count = (image.width * image.height) as usize;
+    if p.len() < count * channels * bytes {
+        return None;
+    }
+    let mut rgb = Vec::with_capacity(3 * count);
+    for i in 0..count {
+        for c in 0..3 {
+            // grey images repeat their first channel, red-green ones have no blue
+            let v = match channels {
+                1 => read(i * bytes),
+                2 if c == 2 => 0.,
+                _ => read((i * channels + c) * bytes),
+            };
+            rgb.push((v * factor[c] * 255.).clamp(0., 255.) as u8);
+        }
+    }
+    Some(rgb)
+}
+
+/// mean color of RGB8 pixels
+fn average(pixels: &[u8]) -> Vec3 {
+    let mut sum = [0u64; 3];
+    for (i, &p) in pixels.iter().enumerate() {
+        sum[i % 3] += p as u64;
+    }
+    let count = (pixels.len() / 3).max(1) as f32 * 255.;
+    Vec3::new(sum[0] as f32 / count, sum[1] as f32 / count, sum[2] as f32 / count)
+}
+
+impl<'a> Importer<'a> {
+    /// RGB8 pixels of a texture multiplied by `factor`, with its width and height
+    fn texture_pixels(&self,
+                      info: &gltf::texture::Info,
+                      factor: &[f32; 4])
+                      -> Result<(Vec<u8>, usize, usize), LoadError> {
+        if info.tex_coord() != 0 {
+            return Err(LoadError::invalid(self.file, "only TEXCOORD_0 is supported for textures"));
+        }
+        let image = &self.images[info.texture().source().index()];
+        let pixels = to_rgb8(image, factor)
+            .ok_or_else(|| LoadError::invalid(self.file, "image data is shorter than its size"))?;
+        Ok((pixels, image.width as usize, image.height as usize))
+    }
+
+    /// emissive -> DiffuseLight, transmissive or blended with alpha < 1 -> Dielectric,
+    /// mostly metallic -> Metal with roughness as fuzz, otherwise Lambertian. metalness and
+    /// roughness are the factors times the mean of the metallic-roughness texture, so a
+    /// material that leaves `metallicFactor` at its default of 1 without a texture is a metal,
+    /// as the spec says. metals take the mean of their base color texture, since `Metal` has
+    /// a single albedo. primitives without a material are diffuse.
+    fn material(&mut self, m: gltf::Material) -> Result<Box<Material>, LoadError> {
+        if let Some(mat) = self.materials.get(&m.index()) {
+            return Ok(mat.clone());
+        }
+        let pbr = m.pbr_metallic_roughness();
+        let base = pbr.base_color_factor();
+        let emissive = m.emissive_factor();
+        let strength = m.emissive_strength().unwrap_or(1.);
+        let transmission = m.transmission().map(|t| t.transmission_factor()).unwrap_or(0.);
+        let (mut metallic, mut roughness) = (pbr.metallic_factor(), pbr.roughness_factor());
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            // roughness is stored in the green channel, metalness in the blue one
+            let (pixels, _, _) = self.texture_pixels(&info, &[1., 1., 1., 1.])?;
+            let mean = average(&pixels);
+            roughness *= mean.y();
+            metallic *= mean.z();
+        }
+        if m.index().is_none() {
+            // the spec's default material is fully metallic, which turns untextured
+            // models into mirrors
+            metallic = 0.;
+        }
+        let base_texture = match pbr.base_color_texture() {
+            Some(info) => Some(self.texture_pixels(&info, &base)?),
+            None => None,
+        };
+        let mat: Box<Material> = if emissive.iter().any(|&e| e > 0.) {
+            let e = Vec3::new(emissive[0], emissive[1], emissive[2]) * strength;
+            Box::new(DiffuseLight::new(Box::new(ConstantTexture::new(e))))
+        } else if transmission > 0. || (m.alpha_mode() == AlphaMode::Blend && base[3] < 1.) {
+            Box::new(Dielectric::new(m.ior().unwrap_or(1.5)))
+        } else if metallic >= 0.5 {
+            let albedo = match base_texture {
+                Some((pixels, _, _)) => average(&pixels),
+                None => Vec3::new(base[0], base[1], base[2]),
+            };
+            Box::new(Metal::new(albedo, roughness.min(1.)))
+        } else {
+            let texture: Box<Texture> = match base_texture {
+                Some((pixels, width, height)) => Box::new(ImageTexture::new(pixels, width, height)),
+                None => Box::new(ConstantTexture::new(Vec3::new(base[0], base[1], base[2]))),
+            };
+            Box::new(Lambertian::new(texture))
+        };
+        self.materials.insert(m.index(), mat.clone());
+        Ok(mat)
+    }
+
+    fn primitive(&mut self, p: &gltf::Primitive) -> Result<Option<TriangleMesh>, LoadError> {
+        let buffers = self.buffers;
+        let reader = p.reader(|b| Some(&buffers[b.index()]));
+        let positions: Vec<Vec3> = match reader.read_positions() {
+            Some(it) => it.map(|v| Vec3::new(v[0], v[1], v[2])).collect(),
+            None => return Ok(None),
+        };
+        let order: Vec<u32> = match reader.read_indices() {
+            Some(it) => it.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if let Some(&i) = order.iter().find(|&&i| i as usize >= positions.len()) {
+            let message = format!("vertex index {} out of range ({} defined)", i, positions.len());
+            return Err(LoadError::invalid(self.file, message));
+        }
+        let indices: Vec<[u32; 3]> = match p.mode() {
+            Mode::Triangles => order.chunks(3).filter(|c| c.len() == 3).map(|c| [c[0], c[1], c[2]]).collect(),
+            Mode::TriangleStrip => {
+                (2..order.len())
+                    .map(|i| if i % 2 == 0 {
+                        [order[i - 2], order[i - 1], order[i]]
+                    } else {
+                        [order[i - 1], order[i - 2], order[i]]
+                    })
+                    .collect()
+            }
+            Mode::TriangleFan => (2..order.len()).map(|i| [order[0], order[i - 1], order[i]]).collect(),
+            // points and lines have no area to hit
+            _ => return Ok(None),
+        };
+        if indices.is_empty() {
+            return Ok(None);
+        }
+        let data = MeshData {
+            positions: positions,
+            normals: reader.read_normals()
+                .map(|it| it.map(|n| Vec3::new(n[0], n[1], n[2])).collect())
+                .unwrap_or_default(),
+            // glTF puts the origin of texture space at the top left of the image
+            uvs: reader.read_tex_coords(0)
+                .map(|it| it.into_f32().map(|t| (t[0], 1. - t[1])).collect())
+                .unwrap_or_default(),
+            indices: indices,
+            ..MeshData::default()
+        };
+        if (!data.normals.is_empty() && data.normals.len() != data.positions.len()) ||
+           (!data.uvs.is_empty() && data.uvs.len() != data.positions.len()) {
+            return Err(LoadError::invalid(self.file, "vertex attributes differ in length"));
+        }
+        let mat = self.material(p.material())?;
+        Ok(Some(TriangleMesh::new(data, mat)))
+    }
+
+    fn node(&mut self, node: Node, parent: &Mat4) -> Result<(), LoadError> {
+        // glTF matrices are column major
+        let local = Mat4::new(node.transform().matrix()).transpose();
+        let m = *parent * local;
+        if let Some(mesh) = node.mesh() {
+            for p in mesh.primitives() {
+                let key = (mesh.index(), p.index());
+                if !self.meshes.contains_key(&key) {
+                    match self.primitive(&p)? {
+                        Some(tm) => {
+                            self.meshes.insert(key, Arc::new(tm));
+                        }
+                        None => continue,
+                    }
+                }
+                let instance = Transform::new(self.meshes[&key].clone(), m).ok_or_else(|| {
+                    LoadError::invalid(self.file, format!("node {} has a singular transform", node.index()))
+                })?;
+                self.world.push(Box::new(instance));
+            }
+        }
+        if let Some(camera) = node.camera() {
+            if let Projection::Perspective(p) = camera.projection() {
+                // cameras look down -z with +y up in their local frame
+                let eye = m.transform_point(&Vec3::new(0., 0., 0.));
+                let lookat = eye.clone() + m.transform_vector(&Vec3::new(0., 0., -1.));
+                let vup = m.transform_vector(&Vec3::new(0., 1., 0.));
+                let aspect = p.aspect_ratio().unwrap_or(self.default_aspect);
+                let vfov = p.yfov().to_degrees();
+                self.cameras.push(Camera::new(&eye, &lookat, &vup, vfov, aspect, 0., 1., 0., 1.));
+            }
+        }
+        for child in node.children() {
+            self.node(child, &m)?;
+        }
+        Ok(())
+    }
+}
+
+fn import(document: Document,
+          buffers: Vec<buffer::Data>,
+          images: Vec<ImageData>,
+          file: &str,
+          default_aspect: f32)
+          -> Result<GltfScene, LoadError> {
+    let scene = match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(s) => s,
+        None => return Err(LoadError::invalid(file, "no scene")),
+    };
+    let mut importer = Importer {
+        file: file,
+        buffers: &buffers,
+        images: &images,
+        default_aspect: default_aspect,
+        meshes: HashMap::new(),
+        materials: HashMap::new(),
+        world: vec![],
+        cameras: vec![],
+    };
+    for node in scene.nodes() {
+        importer.node(node, &Mat4::identity())?;
+    }
+    Ok(GltfScene {
+        world: HitableList::new(importer.world),
+        cameras: importer.cameras,
+    })
+}
+
+fn convert_error(file: &str, e: gltf::Error) -> LoadError {
+    match e {
+        gltf::Error::Io(e) => LoadError::Io(file.to_string(), e),
+        e => LoadError::invalid(file, e.to_string()),
+    }
+}
+
+/// imports the default scene of a `.gltf` or `.glb` asset held in memory. external buffers
+/// and images cannot be resolved, so a `.gltf` must embed them as data URIs.
+/// primitives shared between nodes are instanced through `Transform`. cameras without an
+/// aspect ratio use `default_aspect`, orthographic cameras are skipped.
+pub fn parse_gltf(bytes: &[u8], file: &str, default_aspect: f32) -> Result<GltfScene, LoadError> {
+    let (document, buffers, images) = gltf::import_slice(bytes).map_err(|e| convert_error(file, e))?;
+    import(document, buffers, images, file, default_aspect)
+}
+
+/// loads a `.gltf` or `.glb` file, along with the buffers and images it references
+/// relative to its directory
+pub fn load_gltf(path: &Path, default_aspect: f32) -> Result<GltfScene, LoadError> {
+    let name = path.display().to_string();
+    let (document, buffers, images) = gltf::import(path).map_err(|e| convert_error(&name, e))?;
+    import(document, buffers, images, &name, default_aspect)
+}
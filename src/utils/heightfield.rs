@@ -0,0 +1,218 @@
+use std::f32;
+use std::sync::Arc;
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::{unit_vector, Vec3};
+use utils::ray::Ray;
+use utils::material::Material;
+use utils::aabb::AABB;
+use utils::perlin::Perlin;
+use utils::pnm::GrayImage;
+use utils::triangle::ray_triangle;
+
+/// terrain over a regular `nx * nz` grid of heights in `[0, 1]`, which span `min.y` to
+/// `max.y` while the grid spans `min` to `max` in x and z. every cell is split into two
+/// triangles, shaded with normals from the slope of the grid. `u` runs along x and `v`
+/// along z. rays walk the cells they pass over with a 2D DDA and skip cells whose height
+/// range they miss. heights are shared between clones.
+#[derive(Clone)]
+pub struct Heightfield {
+    nx: usize,
+    nz: usize,
+    heights: Arc<Vec<f32>>,
+    /// lowest and highest world space y of every cell
+    ranges: Arc<Vec<(f32, f32)>>,
+    min: Vec3,
+    max: Vec3,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl Heightfield {
+    pub fn new(nx: usize, nz: usize, heights: Vec<f32>, min: Vec3, max: Vec3, m: Box<Material>) -> Self {
+        assert!(nx > 1 && nz > 1 && heights.len() == nx * nz,
+                "Heightfield needs at least 2x2 heights, one per grid point");
+        let y = |h: f32| min.y() + h * (max.y() - min.y());
+        let mut ranges = Vec::with_capacity((nx - 1) * (nz - 1));
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                let corners = [heights[j * nx + i], heights[j * nx + i + 1], heights[(j + 1) * nx + i],
+                               heights[(j + 1) * nx + i + 1]];
+                let lo = corners.iter().fold(f32::MAX, |a, &b| a.min(b));
+                let hi = corners.iter().fold(-f32::MAX, |a, &b| a.max(b));
+                ranges.push((y(lo), y(hi)));
+            }
+        }
+        Self {
+            nx: nx,
+            nz: nz,
+            heights: Arc::new(heights),
+            ranges: Arc::new(ranges),
+            min: min,
+            max: max,
+            mat: m,
+        }
+    }
+
+    /// grayscale image as elevation map, its top row along `min.z`. an image only one pixel
+    /// wide or high is stretched across the grid, an empty one gives a flat plane at `min.y`.
+    pub fn from_image(image: &GrayImage, min: Vec3, max: Vec3, m: Box<Material>) -> Self {
+        let (width, height) = (image.width, image.height);
+        if width > 1 && height > 1 {
+            return Self::new(width, height, image.pixels.clone(), min, max, m);
+        }
+        let (nx, nz) = (width.max(2), height.max(2));
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                heights.push(if width == 0 || height == 0 {
+                    0.
+                } else {
+                    image.pixels[j.min(height - 1) * width + i.min(width - 1)]
+                });
+            }
+        }
+        Self::new(nx, nz, heights, min, max, m)
+    }
+
+    /// turbulence sampled at `scale` times the world space position of every grid point
+    pub fn from_perlin(nx: usize, nz: usize, scale: f32, depth: i32, min: Vec3, max: Vec3, m: Box<Material>) -> Self {
+        let noise = Perlin::new();
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let x = min.x() + (max.x() - min.x()) * i as f32 / (nx - 1) as f32;
+                let z = min.z() + (max.z() - min.z()) * j as f32 / (nz - 1) as f32;
+                heights.push(noise.turb(Vec3::new(x, 0., z) * scale, depth).min(1.));
+            }
+        }
+        Self::new(nx, nz, heights, min, max, m)
+    }
+
+    /// padded in y like the rects, so that flat terrain still has a box a ray can enter
+    fn bounds(&self) -> AABB {
+        AABB::new(Vec3::new(self.min.x(), self.min.y() - 0.0001, self.min.z()),
+                  Vec3::new(self.max.x(), self.max.y() + 0.0001, self.max.z()))
+    }
+
+    fn cell_size(&self) -> (f32, f32) {
+        ((self.max.x() - self.min.x()) / (self.nx - 1) as f32, (self.max.z() - self.min.z()) / (self.nz - 1) as f32)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        Vec3::new(self.min.x() + i as f32 * dx,
+                  self.min.y() + self.heights[j * self.nx + i] * (self.max.y() - self.min.y()),
+                  self.min.z() + j as f32 * dz)
+    }
+
+    /// central differences, one sided at the border
+    fn vertex_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.nz - 1));
+        let along_x = self.vertex(i1, j) - self.vertex(i0, j);
+        let along_z = self.vertex(i, j1) - self.vertex(i, j0);
+        // cross(along_z, along_x) without the terms that vanish on a grid
+        unit_vector(Vec3::new(-along_x.y() * along_z.z(),
+                              along_x.x() * along_z.z(),
+                              -along_z.y() * along_x.x()))
+    }
+
+    /// closest hit with the two triangles of cell `(i, j)`
+    fn hit_cell(&self, i: usize, j: usize, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let corners = [(i, j), (i, j + 1), (i + 1, j + 1), (i + 1, j)];
+        let mut closest = t_max;
+        let mut found = None;
+        for tri in [[0, 1, 2], [0, 2, 3]].iter() {
+            let (a, b, c) = (corners[tri[0]], corners[tri[1]], corners[tri[2]]);
+            let (v0, v1, v2) = (self.vertex(a.0, a.1), self.vertex(b.0, b.1), self.vertex(c.0, c.1));
+            if let Some((t, b1, b2)) = ray_triangle(&v0, &v1, &v2, r, t_min, closest) {
+                closest = t;
+                found = Some(([a, b, c], b1, b2));
+            }
+        }
+        let (v, b1, b2) = match found {
+            Some(f) => f,
+            None => return false,
+        };
+        let b0 = 1. - b1 - b2;
+        rec.t = closest;
+        rec.p = r.point_at_parameter(closest);
+        rec.u = (rec.p.x() - self.min.x()) / (self.max.x() - self.min.x());
+        rec.v = (rec.p.z() - self.min.z()) / (self.max.z() - self.min.z());
+        rec.normal = unit_vector(self.vertex_normal(v[0].0, v[0].1) * b0 + self.vertex_normal(v[1].0, v[1].1) * b1 +
+                                 self.vertex_normal(v[2].0, v[2].1) * b2);
+        rec.mat = self.mat.clone();
+        true
+    }
+}
+
+impl Hitable for Heightfield {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let (t0, t1) = match self.bounds().clip(r, t_min, t_max) {
+            Some(span) => span,
+            None => return false,
+        };
+        let (dx, dz) = self.cell_size();
+        let (cells_x, cells_z) = (self.nx - 1, self.nz - 1);
+        let (ox, oy, oz) = (r.origin().x(), r.origin().y(), r.origin().z());
+        let (rx, ry, rz) = (r.direction().x(), r.direction().y(), r.direction().z());
+
+        // cell the ray starts in, and the ray parameter of the next x and z cell boundary
+        let start = r.point_at_parameter(t0);
+        let cell = |p: f32, lo: f32, size: f32, n: usize| (((p - lo) / size).floor().max(0.) as usize).min(n - 1);
+        let mut i = cell(start.x(), self.min.x(), dx, cells_x);
+        let mut j = cell(start.z(), self.min.z(), dz, cells_z);
+        let boundary = |index: usize, o: f32, d: f32, lo: f32, size: f32| if d > 0. {
+            (lo + (index + 1) as f32 * size - o) / d
+        } else if d < 0. {
+            (lo + index as f32 * size - o) / d
+        } else {
+            f32::MAX
+        };
+        let mut next_x = boundary(i, ox, rx, self.min.x(), dx);
+        let mut next_z = boundary(j, oz, rz, self.min.z(), dz);
+        let (step_x, step_z) = ((dx / rx).abs(), (dz / rz).abs());
+
+        let mut enter = t0;
+        loop {
+            let exit = next_x.min(next_z).min(t1);
+            // only look at the triangles when the ray's height over the cell overlaps theirs
+            let (ya, yb) = (oy + enter * ry, oy + exit * ry);
+            let (lo, hi) = self.ranges[j * cells_x + i];
+            if ya.min(yb) <= hi && ya.max(yb) >= lo && self.hit_cell(i, j, r, t_min, t_max, rec) {
+                return true;
+            }
+            if exit >= t1 {
+                return false;
+            }
+            if next_x < next_z {
+                if rx > 0. && i + 1 < cells_x {
+                    i += 1;
+                } else if rx < 0. && i > 0 {
+                    i -= 1;
+                } else {
+                    return false;
+                }
+                next_x += step_x;
+            } else {
+                if rz > 0. && j + 1 < cells_z {
+                    j += 1;
+                } else if rz < 0. && j > 0 {
+                    j -= 1;
+                } else {
+                    return false;
+                }
+                next_z += step_z;
+            }
+            enter = exit;
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = self.bounds();
+        true
+    }
+}
@@ -1,7 +1,10 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
 use std::vec::Vec;
-use utils::vec3::Vec3;
+use utils::vec3::{unit_vector, Vec3};
+use utils::matrix::Mat4;
 use utils::ray::Ray;
-use utils::material::Material;
+use utils::material::{DummyMat, Material};
 use utils::aabb::{AABB, surrounding_box};
 
 #[allow(dead_code)]
@@ -29,10 +32,36 @@ impl HitRecord {
     }
 }
 
+/// step past a crossing found by `hit` before looking for the next one. the relative part
+/// keeps the step from vanishing in the rounding of large `t`.
+const CROSSING_EPSILON: f32 = 0.0001;
+const CROSSING_RELATIVE_EPSILON: f32 = 0.000001;
+
 pub trait Hitable {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool;
     fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool;
     fn box_clone(&self) -> Box<Hitable>;
+
+    /// every crossing of the surface inside `(t_min, t_max)`, nearest first. on a closed
+    /// surface they alternate between entering and leaving. the default calls `hit`
+    /// repeatedly, primitives that know all their roots report them directly.
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let mut crossings = vec![];
+        let mut t = t_min;
+        let mut rec = HitRecord::new(Box::new(DummyMat::new()));
+        while self.hit(r, t, t_max, &mut rec) {
+            if !rec.t.is_finite() {
+                break;
+            }
+            let next = rec.t + CROSSING_EPSILON + rec.t.abs() * CROSSING_RELATIVE_EPSILON;
+            crossings.push(rec.clone());
+            if next <= t {
+                break;
+            }
+            t = next;
+        }
+        crossings
+    }
 }
 
 impl Clone for Box<Hitable> {
@@ -75,6 +104,38 @@ impl Hitable for HitableList {
         hit_anything
     }
 
+    /// the children are taken as closed solids that may overlap, only the crossings where
+    /// their union is entered or left are kept
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        // every crossing with +1 when it enters its child and -1 when it leaves it
+        let mut events: Vec<(HitRecord, i32)> = vec![];
+        let mut depth = 0;
+        for h in self.list.iter() {
+            let child = h.crossings(r, t_min, f32::MAX);
+            let mut inside = child.len() % 2 == 1;
+            if inside {
+                depth += 1;
+            }
+            for rec in child.into_iter() {
+                events.push((rec, if inside { -1 } else { 1 }));
+                inside = !inside;
+            }
+        }
+        events.sort_by(|a, b| a.0.t.partial_cmp(&b.0.t).unwrap());
+        let mut crossings = vec![];
+        for (rec, step) in events.into_iter() {
+            if rec.t >= t_max {
+                break;
+            }
+            let was_inside = depth > 0;
+            depth += step;
+            if was_inside != (depth > 0) {
+                crossings.push(rec);
+            }
+        }
+        crossings
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
         if self.list.len() < 1 {
             return false;
@@ -88,8 +149,8 @@ impl Hitable for HitableList {
             *vox = temp_box.clone();
         }
 
-        for _ in 1..self.list.len() {
-            if self.list[0].bounding_box(t0, t1, &mut temp_box) {
+        for h in self.list.iter().skip(1) {
+            if h.bounding_box(t0, t1, &mut temp_box) {
                 *vox = surrounding_box(vox.clone(), temp_box.clone());
             } else {
                 return false;
@@ -99,3 +160,269 @@ impl Hitable for HitableList {
         true
     }
 }
+
+#[derive(Clone)]
+pub struct FlipNormals {
+    ptr: Box<Hitable>,
+}
+
+#[allow(dead_code)]
+impl FlipNormals {
+    pub fn new(p: Box<Hitable>) -> Self {
+        Self { ptr: p }
+    }
+}
+
+impl Hitable for FlipNormals {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        if self.ptr.hit(r, t_min, t_max, rec) {
+            rec.normal = rec.normal.clone() * -1.;
+            return true;
+        }
+        false
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let mut crossings = self.ptr.crossings(r, t_min, t_max);
+        for rec in crossings.iter_mut() {
+            rec.normal = rec.normal.clone() * -1.;
+        }
+        crossings
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
+        self.ptr.bounding_box(t0, t1, vox)
+    }
+}
+
+#[derive(Clone)]
+pub struct Translate {
+    ptr: Box<Hitable>,
+    offset: Vec3,
+}
+
+#[allow(dead_code)]
+impl Translate {
+    pub fn new(p: Box<Hitable>, displacement: Vec3) -> Self {
+        Self {
+            ptr: p,
+            offset: displacement,
+        }
+    }
+
+    fn local_ray(&self, r: &Ray) -> Ray {
+        Ray::new(&(r.origin().clone() - self.offset.clone()), r.direction(), r.clone().time())
+    }
+}
+
+impl Hitable for Translate {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        if self.ptr.hit(&self.local_ray(r), t_min, t_max, rec) {
+            rec.p = rec.p.clone() + self.offset.clone();
+            return true;
+        }
+        false
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let mut crossings = self.ptr.crossings(&self.local_ray(r), t_min, t_max);
+        for rec in crossings.iter_mut() {
+            rec.p = rec.p.clone() + self.offset.clone();
+        }
+        crossings
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
+        if self.ptr.bounding_box(t0, t1, vox) {
+            *vox = AABB::new(vox.clone().min() + self.offset.clone(),
+                             vox.clone().max() + self.offset.clone());
+            return true;
+        }
+        false
+    }
+}
+
+#[derive(Clone)]
+pub struct RotateY {
+    ptr: Box<Hitable>,
+    sin_theta: f32,
+    cos_theta: f32,
+    hasbox: bool,
+    bbox: AABB,
+}
+
+#[allow(dead_code)]
+impl RotateY {
+    pub fn new(p: Box<Hitable>, angle: f32) -> Self {
+        let radians = (PI / 180.) * angle;
+        let sin_theta = radians.sin();
+        let cos_theta = radians.cos();
+        let mut bbox = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
+        let hasbox = p.bounding_box(0., 1., &mut bbox);
+        let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vec3::new(-f32::MAX, -f32::MAX, -f32::MAX);
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let x = i as f32 * bbox.clone().max().x() + (1 - i) as f32 * bbox.clone().min().x();
+                    let y = j as f32 * bbox.clone().max().y() + (1 - j) as f32 * bbox.clone().min().y();
+                    let z = k as f32 * bbox.clone().max().z() + (1 - k) as f32 * bbox.clone().min().z();
+                    let newx = cos_theta * x + sin_theta * z;
+                    let newz = -sin_theta * x + cos_theta * z;
+                    let tester = Vec3::new(newx, y, newz);
+                    for c in 0..3 {
+                        if tester.e[c] > max.e[c] {
+                            max.e[c] = tester.e[c];
+                        }
+                        if tester.e[c] < min.e[c] {
+                            min.e[c] = tester.e[c];
+                        }
+                    }
+                }
+            }
+        }
+        Self {
+            ptr: p,
+            sin_theta: sin_theta,
+            cos_theta: cos_theta,
+            hasbox: hasbox,
+            bbox: AABB::new(min, max),
+        }
+    }
+
+    fn local_ray(&self, r: &Ray) -> Ray {
+        let mut origin = r.origin().clone();
+        let mut direction = r.direction().clone();
+        origin.e[0] = self.cos_theta * r.origin().x() - self.sin_theta * r.origin().z();
+        origin.e[2] = self.sin_theta * r.origin().x() + self.cos_theta * r.origin().z();
+        direction.e[0] = self.cos_theta * r.direction().x() - self.sin_theta * r.direction().z();
+        direction.e[2] = self.sin_theta * r.direction().x() + self.cos_theta * r.direction().z();
+        Ray::new(&origin, &direction, r.clone().time())
+    }
+
+    fn to_world(&self, rec: &mut HitRecord) {
+        let mut p = rec.p.clone();
+        let mut normal = rec.normal.clone();
+        p.e[0] = self.cos_theta * rec.p.x() + self.sin_theta * rec.p.z();
+        p.e[2] = -self.sin_theta * rec.p.x() + self.cos_theta * rec.p.z();
+        normal.e[0] = self.cos_theta * rec.normal.x() + self.sin_theta * rec.normal.z();
+        normal.e[2] = -self.sin_theta * rec.normal.x() + self.cos_theta * rec.normal.z();
+        rec.p = p;
+        rec.normal = normal;
+    }
+}
+
+impl Hitable for RotateY {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        if self.ptr.hit(&self.local_ray(r), t_min, t_max, rec) {
+            self.to_world(rec);
+            return true;
+        }
+        false
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let mut crossings = self.ptr.crossings(&self.local_ray(r), t_min, t_max);
+        for rec in crossings.iter_mut() {
+            self.to_world(rec);
+        }
+        crossings
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = self.bbox.clone();
+        self.hasbox
+    }
+}
+
+/// affine instance of a shared hitable. the inner object is held by `Arc` so that
+/// one heavy object can be placed many times without copying its geometry.
+/// `new` returns `None` for a matrix that is not invertible.
+#[derive(Clone)]
+pub struct Transform {
+    ptr: Arc<Hitable>,
+    m: Mat4,
+    inv: Mat4,
+    normal_m: Mat4,
+}
+
+#[allow(dead_code)]
+impl Transform {
+    pub fn new(p: Arc<Hitable>, m: Mat4) -> Option<Self> {
+        let inv = m.inverse()?;
+        Some(Self {
+            ptr: p,
+            m: m,
+            inv: inv,
+            normal_m: inv.transpose(),
+        })
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.m
+    }
+
+    fn local_ray(&self, r: &Ray) -> Ray {
+        // direction is not normalized, so `t` is the same in object and world space
+        let origin = self.inv.transform_point(r.origin());
+        let direction = self.inv.transform_vector(r.direction());
+        Ray::new(&origin, &direction, r.clone().time())
+    }
+
+    fn to_world(&self, rec: &mut HitRecord) {
+        rec.p = self.m.transform_point(&rec.p);
+        rec.normal = unit_vector(self.normal_m.transform_vector(&rec.normal));
+    }
+}
+
+impl Hitable for Transform {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        if self.ptr.hit(&self.local_ray(r), t_min, t_max, rec) {
+            self.to_world(rec);
+            return true;
+        }
+        false
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let mut crossings = self.ptr.crossings(&self.local_ray(r), t_min, t_max);
+        for rec in crossings.iter_mut() {
+            self.to_world(rec);
+        }
+        crossings
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
+        let mut bbox = AABB::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
+        if !self.ptr.bounding_box(t0, t1, &mut bbox) {
+            return false;
+        }
+        let (bmin, bmax) = (bbox.clone().min(), bbox.max());
+        let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vec3::new(-f32::MAX, -f32::MAX, -f32::MAX);
+        for i in 0..8 {
+            let corner = Vec3::new(if i & 1 == 0 { bmin.x() } else { bmax.x() },
+                                   if i & 2 == 0 { bmin.y() } else { bmax.y() },
+                                   if i & 4 == 0 { bmin.z() } else { bmax.z() });
+            let tester = self.m.transform_point(&corner);
+            for c in 0..3 {
+                min.e[c] = min.e[c].min(tester.e[c]);
+                max.e[c] = max.e[c].max(tester.e[c]);
+            }
+        }
+        *vox = AABB::new(min, max);
+        true
+    }
+}
@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// error returned by the model loaders. parse errors carry the 1-based line
+/// (or, for binary files, element) number where the input went wrong, `Invalid`
+/// is used for problems that cannot be pinned to a position.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(String, io::Error),
+    Parse { file: String, line: usize, message: String },
+    Invalid(String, String),
+}
+
+impl LoadError {
+    pub fn parse<S: Into<String>>(file: &str, line: usize, message: S) -> Self {
+        LoadError::Parse {
+            file: file.to_string(),
+            line: line,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid<S: Into<String>>(file: &str, message: S) -> Self {
+        LoadError::Invalid(file.to_string(), message.into())
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref file, ref e) => write!(f, "{}: {}", file, e),
+            LoadError::Parse { ref file, line, ref message } => write!(f, "{}:{}: {}", file, line, message),
+            LoadError::Invalid(ref file, ref message) => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            LoadError::Io(_, ref e) => Some(e),
+            LoadError::Parse { .. } | LoadError::Invalid(..) => None,
+        }
+    }
+}
@@ -62,7 +62,7 @@ impl Material for Lambertian {
     fn scatter(&self, _r_in: &Ray, rec: &HitRecord, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
         let target = rec.normal.clone() + random_in_unit_sphere();
         *scattered = Ray::new(&rec.p, &target, 0.).clone();
-        *attenuation = self.albedo.value(0., 0., rec.p.clone());
+        *attenuation = self.albedo.value(rec.u, rec.v, rec.p.clone());
         true
     }
     fn emitted(&self, _u: f32, _v: f32, _p: Vec3) -> Vec3 {
@@ -205,6 +205,36 @@ impl Material for DiffuseLight {
     }
 }
 
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Isotropic {
+    albedo: Box<Texture>,
+}
+
+#[allow(dead_code)]
+impl Isotropic {
+    pub fn new(a: Box<Texture>) -> Self {
+        Self { albedo: a }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        *scattered = Ray::new(&rec.p, &random_in_unit_sphere(), r_in.clone().time());
+        *attenuation = self.albedo.value(rec.u, rec.v, rec.p.clone());
+        true
+    }
+    fn emitted(&self, _u: f32, _v: f32, _p: Vec3) -> Vec3 {
+        Vec3::new(0., 0., 0.)
+    }
+    fn box_clone(&self) -> Box<Material> {
+        Box::new((*self).clone())
+    }
+    fn name(&self) -> String {
+        "isotropic".to_string()
+    }
+}
+
 pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
     v.clone() - n.clone() * dot(v, n) * 2.
 }
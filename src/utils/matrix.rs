@@ -0,0 +1,118 @@
+use std::ops::Mul;
+use utils::vec3::{unit_vector, Vec3};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+#[allow(dead_code)]
+impl Mat4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Self { m: m }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]])
+    }
+
+    pub fn translate(v: &Vec3) -> Self {
+        Self::new([[1., 0., 0., v.x()], [0., 1., 0., v.y()], [0., 0., 1., v.z()], [0., 0., 0., 1.]])
+    }
+
+    pub fn scale(v: &Vec3) -> Self {
+        Self::new([[v.x(), 0., 0., 0.], [0., v.y(), 0., 0.], [0., 0., v.z(), 0.], [0., 0., 0., 1.]])
+    }
+
+    /// rotation of `angle` degrees around an arbitrary `axis` (Rodrigues' formula)
+    pub fn rotate(axis: &Vec3, angle: f32) -> Self {
+        let a = unit_vector(axis.clone());
+        let radians = angle.to_radians();
+        let (s, c) = (radians.sin(), radians.cos());
+        let t = 1. - c;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        Self::new([[t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.],
+                   [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.],
+                   [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.],
+                   [0., 0., 0., 1.]])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut r = [[0f32; 4]; 4];
+        for (i, row) in r.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[j][i];
+            }
+        }
+        Self::new(r)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting. returns `None` for singular matrices.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+        for col in 0..4 {
+            let mut pivot = col;
+            for row in (col + 1)..4 {
+                if a[row][col].abs() > a[pivot][col].abs() {
+                    pivot = row;
+                }
+            }
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let d = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= d;
+                inv[col][j] /= d;
+            }
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let f = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1. {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x / w, y / w, z / w)
+        }
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+                  m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+                  m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z())
+    }
+}
+
+impl Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut r = [[0f32; 4]; 4];
+        for (i, row) in r.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4::new(r)
+    }
+}
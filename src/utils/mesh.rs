@@ -0,0 +1,163 @@
+use std::mem;
+use std::sync::Arc;
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::{cross, unit_vector, Vec3};
+use utils::ray::Ray;
+use utils::material::{Lambertian, Material};
+use utils::texture::ConstantTexture;
+use utils::aabb::AABB;
+use utils::bvh::IndexBVH;
+use utils::triangle::{ray_triangle, triangle_bounds};
+
+/// vertex and index buffers of a triangle mesh. `normals`, `uvs` and `colors` are either
+/// empty or hold one entry per position.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub colors: Vec<Vec3>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+#[allow(dead_code)]
+impl MeshData {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[u32; 3]>) -> Self {
+        Self {
+            positions: positions,
+            indices: indices,
+            ..Self::default()
+        }
+    }
+
+    pub fn triangle(&self, i: usize) -> (&Vec3, &Vec3, &Vec3) {
+        let [a, b, c] = self.indices[i];
+        (&self.positions[a as usize], &self.positions[b as usize], &self.positions[c as usize])
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        // every `Vec3` keeps its three components in a separate allocation
+        let vec3 = mem::size_of::<Vec3>() + 3 * mem::size_of::<f32>();
+        mem::size_of::<Self>() + (self.positions.len() + self.normals.len() + self.colors.len()) * vec3 +
+        self.uvs.len() * mem::size_of::<(f32, f32)>() + self.indices.len() * mem::size_of::<[u32; 3]>()
+    }
+}
+
+/// indexed triangle mesh exposed as a single hitable. buffers and the BVH over its
+/// triangles are shared between clones. a mesh without triangles is never hit and has
+/// no bounding box.
+#[derive(Clone)]
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    bvh: Arc<IndexBVH>,
+    mat: Box<Material>,
+    vertex_colors: bool,
+}
+
+// shared between instances and render threads like `HitableList`, nothing in it is mutated
+unsafe impl Sync for TriangleMesh {}
+unsafe impl Send for TriangleMesh {}
+
+#[allow(dead_code)]
+impl TriangleMesh {
+    pub fn new(data: MeshData, m: Box<Material>) -> Self {
+        assert!(data.normals.is_empty() || data.normals.len() == data.positions.len(),
+                "TriangleMesh needs one normal per position");
+        assert!(data.uvs.is_empty() || data.uvs.len() == data.positions.len(),
+                "TriangleMesh needs one uv per position");
+        assert!(data.colors.is_empty() || data.colors.len() == data.positions.len(),
+                "TriangleMesh needs one color per position");
+        let bounds: Vec<AABB> = (0..data.indices.len())
+            .map(|i| {
+                let (a, b, c) = data.triangle(i);
+                triangle_bounds(a, b, c)
+            })
+            .collect();
+        Self {
+            bvh: Arc::new(IndexBVH::new(&bounds)),
+            data: Arc::new(data),
+            mat: m,
+            vertex_colors: false,
+        }
+    }
+
+    /// diffuse mesh whose albedo is interpolated from the per-vertex `colors`
+    pub fn with_vertex_colors(data: MeshData) -> Self {
+        assert!(!data.colors.is_empty(), "TriangleMesh::with_vertex_colors needs vertex colors");
+        let placeholder = Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(0., 0., 0.))));
+        let mut mesh = Self::new(data, Box::new(placeholder));
+        mesh.vertex_colors = true;
+        mesh
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.data.indices.len()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.data.memory_usage() + self.bvh.memory_usage()
+    }
+}
+
+impl Hitable for TriangleMesh {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        // (triangle, t, b1, b2) of the closest hit, attributes are only interpolated for that one
+        let mut closest: Option<(usize, f32, f32, f32)> = None;
+        let hit = self.bvh.traverse(r, t_min, t_max, |i, closest_so_far| {
+            let (a, b, c) = self.data.triangle(i);
+            ray_triangle(a, b, c, r, t_min, closest_so_far).map(|(t, b1, b2)| {
+                closest = Some((i, t, b1, b2));
+                t
+            })
+        });
+        let (i, t, b1, b2) = match closest {
+            Some(h) if hit => h,
+            _ => return false,
+        };
+        let idx = self.data.indices[i];
+        let (i0, i1, i2) = (idx[0] as usize, idx[1] as usize, idx[2] as usize);
+        let b0 = 1. - b1 - b2;
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        if self.data.uvs.is_empty() {
+            rec.u = b1;
+            rec.v = b2;
+        } else {
+            let uv = &self.data.uvs;
+            rec.u = b0 * uv[i0].0 + b1 * uv[i1].0 + b2 * uv[i2].0;
+            rec.v = b0 * uv[i0].1 + b1 * uv[i1].1 + b2 * uv[i2].1;
+        }
+        rec.normal = if self.data.normals.is_empty() {
+            let (a, b, c) = self.data.triangle(i);
+            unit_vector(cross(&(b.clone() - a.clone()), &(c.clone() - a.clone())))
+        } else {
+            let n = &self.data.normals;
+            unit_vector(n[i0].clone() * b0 + n[i1].clone() * b1 + n[i2].clone() * b2)
+        };
+        rec.mat = if self.vertex_colors {
+            let c = &self.data.colors;
+            let albedo = c[i0].clone() * b0 + c[i1].clone() * b1 + c[i2].clone() * b2;
+            Box::new(Lambertian::new(Box::new(ConstantTexture::new(albedo))))
+        } else {
+            self.mat.clone()
+        };
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        match self.bvh.bounds() {
+            Some(b) => {
+                *vox = b;
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 extern crate rand;
 
 pub mod vec3;
+pub mod matrix;
 pub mod ray;
 pub mod hitable;
 pub mod sphere;
@@ -12,6 +13,21 @@ pub mod bvh;
 pub mod texture;
 pub mod perlin;
 pub mod rect;
+pub mod box_hitable;
+pub mod volume;
+pub mod triangle;
+pub mod mesh;
+pub mod load;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+pub mod gltf_scene;
+pub mod quadric;
+pub mod torus;
+pub mod csg;
+pub mod sdf;
+pub mod pnm;
+pub mod heightfield;
 
 #[macro_export]
 macro_rules! get_sphere {
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use utils::vec3::Vec3;
+use utils::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use utils::texture::ConstantTexture;
+use utils::mesh::{MeshData, TriangleMesh};
+use utils::load::LoadError;
+
+const DEFAULT_MATERIAL: &str = "";
+
+/// indices of position, texture coordinate and normal of one face corner
+type Corner = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct Group {
+    material: String,
+    corners: Vec<Corner>,
+    lookup: HashMap<Corner, u32>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl Group {
+    fn vertex(&mut self, c: Corner) -> u32 {
+        if let Some(&i) = self.lookup.get(&c) {
+            return i;
+        }
+        let i = self.corners.len() as u32;
+        self.corners.push(c);
+        self.lookup.insert(c, i);
+        i
+    }
+
+    fn into_mesh_data(self, positions: &[Vec3], uvs: &[(f32, f32)], normals: &[Vec3]) -> MeshData {
+        // meshes carry normals and uvs for every vertex or not at all
+        let has_uvs = self.corners.iter().all(|c| c.1.is_some());
+        let has_normals = self.corners.iter().all(|c| c.2.is_some());
+        MeshData {
+            positions: self.corners.iter().map(|c| positions[c.0].clone()).collect(),
+            uvs: if has_uvs {
+                self.corners.iter().map(|c| uvs[c.1.unwrap()]).collect()
+            } else {
+                vec![]
+            },
+            normals: if has_normals {
+                self.corners.iter().map(|c| normals[c.2.unwrap()].clone()).collect()
+            } else {
+                vec![]
+            },
+            indices: self.indices,
+            ..MeshData::default()
+        }
+    }
+}
+
+fn parse_floats(tokens: &[&str], n: usize, keyword: &str, file: &str, line: usize) -> Result<Vec<f32>, LoadError> {
+    if tokens.len() < n {
+        return Err(LoadError::parse(file, line, format!("'{}' needs {} numbers, got {}", keyword, n, tokens.len())));
+    }
+    tokens[..n]
+        .iter()
+        .map(|t| t.parse::<f32>().map_err(|_| LoadError::parse(file, line, format!("invalid number '{}'", t))))
+        .collect()
+}
+
+fn parse_color(tokens: &[&str], keyword: &str, file: &str, line: usize) -> Result<Vec3, LoadError> {
+    let c = parse_floats(tokens, 3, keyword, file, line)?;
+    Ok(Vec3::new(c[0], c[1], c[2]))
+}
+
+/// resolves a 1-based (or negative, relative to the end) OBJ index into `0..len`
+fn resolve_index(token: &str, len: usize, what: &str, file: &str, line: usize) -> Result<usize, LoadError> {
+    let i: i64 = token.parse()
+        .map_err(|_| LoadError::parse(file, line, format!("invalid {} index '{}'", what, token)))?;
+    let resolved = if i > 0 {
+        i - 1
+    } else {
+        len as i64 + i
+    };
+    if i == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(LoadError::parse(file,
+                                    line,
+                                    format!("{} index {} out of range ({} defined)", what, i, len)));
+    }
+    Ok(resolved as usize)
+}
+
+#[derive(Clone)]
+struct MtlParams {
+    kd: Vec3,
+    ks: Vec3,
+    ke: Vec3,
+    ns: f32,
+    ni: f32,
+    d: f32,
+}
+
+impl MtlParams {
+    fn new() -> Self {
+        Self {
+            kd: Vec3::new(0.8, 0.8, 0.8),
+            ks: Vec3::new(0., 0., 0.),
+            ke: Vec3::new(0., 0., 0.),
+            ns: 0.,
+            ni: 1.5,
+            d: 1.,
+        }
+    }
+
+    /// emissive -> DiffuseLight, transparent -> Dielectric, more specular than diffuse -> Metal,
+    /// otherwise Lambertian
+    fn to_material(&self) -> Box<Material> {
+        let max = |v: &Vec3| v.x().max(v.y()).max(v.z());
+        if max(&self.ke) > 0. {
+            Box::new(DiffuseLight::new(Box::new(ConstantTexture::new(self.ke.clone()))))
+        } else if self.d < 1. {
+            Box::new(Dielectric::new(self.ni))
+        } else if max(&self.ks) > max(&self.kd) {
+            let fuzz = (1. - self.ns / 1000.).clamp(0., 1.);
+            Box::new(Metal::new(self.ks.clone(), fuzz))
+        } else {
+            Box::new(Lambertian::new(Box::new(ConstantTexture::new(self.kd.clone()))))
+        }
+    }
+}
+
+/// parses an MTL material library. `file` is only used in error messages.
+pub fn parse_mtl<R: BufRead>(reader: R, file: &str) -> Result<HashMap<String, Box<Material>>, LoadError> {
+    let mut params: Vec<(String, MtlParams)> = vec![];
+    for (n, l) in reader.lines().enumerate() {
+        let line = n + 1;
+        let l = l.map_err(|e| LoadError::Io(file.to_string(), e))?;
+        let tokens: Vec<&str> = l.split('#').next().unwrap().split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        if tokens[0] == "newmtl" {
+            if tokens.len() < 2 {
+                return Err(LoadError::parse(file, line, "'newmtl' needs a name"));
+            }
+            params.push((tokens[1..].join(" "), MtlParams::new()));
+            continue;
+        }
+        let current = match params.last_mut() {
+            Some(&mut (_, ref mut p)) => p,
+            None => return Err(LoadError::parse(file, line, format!("'{}' before any 'newmtl'", tokens[0]))),
+        };
+        let args = &tokens[1..];
+        match tokens[0] {
+            "Kd" => current.kd = parse_color(args, "Kd", file, line)?,
+            "Ks" => current.ks = parse_color(args, "Ks", file, line)?,
+            "Ke" => current.ke = parse_color(args, "Ke", file, line)?,
+            "Ns" => current.ns = parse_floats(args, 1, "Ns", file, line)?[0],
+            "Ni" => current.ni = parse_floats(args, 1, "Ni", file, line)?[0],
+            "d" => current.d = parse_floats(args, 1, "d", file, line)?[0],
+            "Tr" => current.d = 1. - parse_floats(args, 1, "Tr", file, line)?[0],
+            // ambient, illumination model, texture maps etc. have no counterpart here
+            _ => {}
+        }
+    }
+    Ok(params.into_iter().map(|(name, p)| (name, p.to_material())).collect())
+}
+
+/// parses OBJ geometry into one mesh per material. polygons are fan triangulated.
+/// `mtllib` statements are resolved through `load_mtl`, which gets the library name.
+pub fn parse_obj<R, F>(reader: R, file: &str, mut load_mtl: F) -> Result<Vec<TriangleMesh>, LoadError>
+    where R: BufRead,
+          F: FnMut(&str) -> Result<HashMap<String, Box<Material>>, LoadError>
+{
+    let mut positions: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f32, f32)> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut materials: HashMap<String, Box<Material>> = HashMap::new();
+    let mut groups: Vec<Group> = vec![];
+    let mut current = 0;
+    groups.push(Group {
+        material: DEFAULT_MATERIAL.to_string(),
+        ..Group::default()
+    });
+
+    for (n, l) in reader.lines().enumerate() {
+        let line = n + 1;
+        let l = l.map_err(|e| LoadError::Io(file.to_string(), e))?;
+        let tokens: Vec<&str> = l.split('#').next().unwrap().split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        let args = &tokens[1..];
+        match tokens[0] {
+            "v" => {
+                let p = parse_floats(args, 3, "v", file, line)?;
+                positions.push(Vec3::new(p[0], p[1], p[2]));
+            }
+            "vt" => {
+                let t = parse_floats(args, 1, "vt", file, line)?;
+                let v = if args.len() > 1 {
+                    parse_floats(&args[1..], 1, "vt", file, line)?[0]
+                } else {
+                    0.
+                };
+                uvs.push((t[0], v));
+            }
+            "vn" => {
+                let v = parse_floats(args, 3, "vn", file, line)?;
+                normals.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(LoadError::parse(file, line, format!("face needs 3 vertices, got {}", args.len())));
+                }
+                let mut face: Vec<u32> = vec![];
+                for a in args.iter() {
+                    let mut parts = a.split('/');
+                    let v = resolve_index(parts.next().unwrap(), positions.len(), "vertex", file, line)?;
+                    let vt = match parts.next() {
+                        Some(t) if !t.is_empty() => Some(resolve_index(t, uvs.len(), "texture", file, line)?),
+                        _ => None,
+                    };
+                    let vn = match parts.next() {
+                        Some(t) if !t.is_empty() => Some(resolve_index(t, normals.len(), "normal", file, line)?),
+                        _ => None,
+                    };
+                    if parts.next().is_some() {
+                        return Err(LoadError::parse(file, line, format!("malformed face vertex '{}'", a)));
+                    }
+                    face.push(groups[current].vertex((v, vt, vn)));
+                }
+                for i in 1..face.len() - 1 {
+                    groups[current].indices.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            "usemtl" => {
+                // materials missing from every library fall back to the default one
+                let name = args.join(" ");
+                current = match groups.iter().position(|g| g.material == name) {
+                    Some(i) => i,
+                    None => {
+                        groups.push(Group {
+                            material: name,
+                            ..Group::default()
+                        });
+                        groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => {
+                for lib in args.iter() {
+                    materials.extend(load_mtl(lib)?);
+                }
+            }
+            // objects, groups, smoothing groups, lines, free-form geometry... are not supported
+            _ => {}
+        }
+    }
+
+    Ok(groups.into_iter()
+        .filter(|g| !g.indices.is_empty())
+        .map(|g| {
+            let mat = match materials.get(&g.material) {
+                Some(m) => m.clone(),
+                None => MtlParams::new().to_material(),
+            };
+            TriangleMesh::new(g.into_mesh_data(&positions, &uvs, &normals), mat)
+        })
+        .collect())
+}
+
+/// loads an OBJ file, along with the MTL libraries it references relative to its directory
+pub fn load_obj(path: &Path) -> Result<Vec<TriangleMesh>, LoadError> {
+    let name = path.display().to_string();
+    let f = File::open(path).map_err(|e| LoadError::Io(name.clone(), e))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    parse_obj(BufReader::new(f), &name, |lib| {
+        let mtl_path = dir.join(lib);
+        let mtl_name = mtl_path.display().to_string();
+        let f = File::open(&mtl_path).map_err(|e| LoadError::Io(mtl_name.clone(), e))?;
+        parse_mtl(BufReader::new(f), &mtl_name)
+    })
+}
@@ -0,0 +1,354 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use utils::vec3::Vec3;
+use utils::mesh::MeshData;
+use utils::load::LoadError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// scale that maps an integer color channel to `[0, 1]`
+    fn color_scale(&self) -> f64 {
+        match *self {
+            Scalar::U8 => 1. / 255.,
+            Scalar::U16 => 1. / 65535.,
+            _ => 1.,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match *self {
+            Property::Scalar(ref n, _) | Property::List(ref n, _, _) => n,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// source of property values for the body of a PLY file
+trait Values {
+    fn next(&mut self, t: Scalar) -> Result<f64, String>;
+    /// position used in error messages: a line for ASCII, an element for binary input
+    fn position(&self) -> usize;
+    fn next_element(&mut self);
+}
+
+struct AsciiValues<'a> {
+    lines: Vec<(usize, &'a str)>,
+    current: usize,
+    tokens: Vec<&'a str>,
+    next_token: usize,
+}
+
+impl<'a> AsciiValues<'a> {
+    fn new(body: &'a str, first_line: usize) -> Self {
+        let lines = body.lines()
+            .enumerate()
+            .map(|(i, l)| (first_line + i, l))
+            .filter(|&(_, l)| !l.trim().is_empty())
+            .collect();
+        Self {
+            lines: lines,
+            current: 0,
+            tokens: vec![],
+            next_token: 0,
+        }
+    }
+}
+
+impl<'a> Values for AsciiValues<'a> {
+    fn next(&mut self, t: Scalar) -> Result<f64, String> {
+        let token = match self.tokens.get(self.next_token) {
+            Some(token) => *token,
+            None => return Err("too few values on line".to_string()),
+        };
+        self.next_token += 1;
+        let v: f64 = token.parse().map_err(|_| format!("invalid number '{}'", token))?;
+        if t != Scalar::F32 && t != Scalar::F64 && v.fract() != 0. {
+            return Err(format!("expected an integer, got '{}'", token));
+        }
+        Ok(v)
+    }
+
+    fn position(&self) -> usize {
+        self.lines.get(self.current).map_or(0, |l| l.0)
+    }
+
+    fn next_element(&mut self) {
+        if !self.tokens.is_empty() {
+            self.current += 1;
+        }
+        self.tokens = self.lines.get(self.current).map_or(vec![], |l| l.1.split_whitespace().collect());
+        self.next_token = 0;
+    }
+}
+
+struct BinaryValues<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    element: usize,
+}
+
+impl<'a> Values for BinaryValues<'a> {
+    fn next(&mut self, t: Scalar) -> Result<f64, String> {
+        let size = t.size();
+        if self.offset + size > self.bytes.len() {
+            return Err("unexpected end of file".to_string());
+        }
+        let b = &self.bytes[self.offset..self.offset + size];
+        self.offset += size;
+        Ok(match t {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]),
+        })
+    }
+
+    fn position(&self) -> usize {
+        self.element
+    }
+
+    fn next_element(&mut self) {
+        self.element += 1;
+    }
+}
+
+/// parsed header and the offset where the body starts
+fn parse_header(bytes: &[u8], file: &str) -> Result<(Format, Vec<Element>, usize, usize), LoadError> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut offset = 0;
+    let mut line = 0;
+    loop {
+        let end = match bytes[offset..].iter().position(|&b| b == b'\n') {
+            Some(end) => offset + end,
+            None => return Err(LoadError::parse(file, line + 1, "missing 'end_header'")),
+        };
+        line += 1;
+        let text = String::from_utf8_lossy(&bytes[offset..end]).into_owned();
+        offset = end + 1;
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        if line == 1 {
+            if tokens != ["ply"] {
+                return Err(LoadError::parse(file, line, "not a PLY file"));
+            }
+            continue;
+        }
+        if tokens.is_empty() {
+            continue;
+        }
+        match tokens[0] {
+            "format" => {
+                format = match tokens.get(1) {
+                    Some(&"ascii") => Some(Format::Ascii),
+                    Some(&"binary_little_endian") => Some(Format::BinaryLittleEndian),
+                    Some(f) => return Err(LoadError::parse(file, line, format!("unsupported format '{}'", f))),
+                    None => return Err(LoadError::parse(file, line, "'format' needs a value")),
+                };
+            }
+            "element" => {
+                if tokens.len() != 3 {
+                    return Err(LoadError::parse(file, line, "'element' needs a name and a count"));
+                }
+                let count = tokens[2]
+                    .parse()
+                    .map_err(|_| LoadError::parse(file, line, format!("invalid element count '{}'", tokens[2])))?;
+                elements.push(Element {
+                    name: tokens[1].to_string(),
+                    count: count,
+                    properties: vec![],
+                });
+            }
+            "property" => {
+                let scalar = |name: &str| {
+                    Scalar::parse(name).ok_or_else(|| LoadError::parse(file, line, format!("unknown type '{}'", name)))
+                };
+                let property = if tokens.get(1) == Some(&"list") && tokens.len() == 5 {
+                    Property::List(tokens[4].to_string(), scalar(tokens[2])?, scalar(tokens[3])?)
+                } else if tokens.len() == 3 {
+                    Property::Scalar(tokens[2].to_string(), scalar(tokens[1])?)
+                } else {
+                    return Err(LoadError::parse(file, line, "malformed 'property'"));
+                };
+                match elements.last_mut() {
+                    Some(e) => e.properties.push(property),
+                    None => return Err(LoadError::parse(file, line, "'property' before any 'element'")),
+                }
+            }
+            "end_header" => break,
+            "comment" | "obj_info" => {}
+            k => return Err(LoadError::parse(file, line, format!("unknown header keyword '{}'", k))),
+        }
+    }
+    match format {
+        Some(f) => Ok((f, elements, offset, line)),
+        None => Err(LoadError::parse(file, line, "missing 'format'")),
+    }
+}
+
+fn find(properties: &[Property], names: &[&str]) -> Option<usize> {
+    properties.iter().position(|p| names.contains(&p.name()))
+}
+
+/// parses an ASCII or binary little-endian PLY file into mesh buffers. normals (`nx ny nz`),
+/// texture coordinates (`u v`, `s t` or `texture_u texture_v`) and vertex colors
+/// (`red green blue`) are read when present; faces are fan triangulated.
+pub fn parse_ply(bytes: &[u8], file: &str) -> Result<MeshData, LoadError> {
+    let (format, elements, body, header_lines) = parse_header(bytes, file)?;
+    let mut values: Box<Values> = match format {
+        Format::Ascii => {
+            let text = ::std::str::from_utf8(&bytes[body..])
+                .map_err(|_| LoadError::parse(file, header_lines + 1, "ASCII body is not valid UTF-8"))?;
+            Box::new(AsciiValues::new(text, header_lines + 1))
+        }
+        Format::BinaryLittleEndian => {
+            Box::new(BinaryValues {
+                bytes: &bytes[body..],
+                offset: 0,
+                element: 0,
+            })
+        }
+    };
+
+    let mut mesh = MeshData::default();
+    for e in elements.iter() {
+        let props = &e.properties;
+        let xyz = [find(props, &["x"]), find(props, &["y"]), find(props, &["z"])];
+        let normal = [find(props, &["nx"]), find(props, &["ny"]), find(props, &["nz"])];
+        let uv = [find(props, &["u", "s", "texture_u"]), find(props, &["v", "t", "texture_v"])];
+        let rgb = [find(props, &["red", "r"]), find(props, &["green", "g"]), find(props, &["blue", "b"])];
+        let face = find(props, &["vertex_indices", "vertex_index"]);
+        if e.name == "vertex" && xyz.iter().any(|i| i.is_none()) {
+            return Err(LoadError::parse(file, header_lines, "vertex element needs x, y and z"));
+        }
+
+        for _ in 0..e.count {
+            values.next_element();
+            let mut scalars = vec![0f64; props.len()];
+            let mut list: Vec<f64> = vec![];
+            for (i, p) in props.iter().enumerate() {
+                let position = values.position();
+                let err = |m: String| LoadError::parse(file, position, m);
+                match *p {
+                    Property::Scalar(_, t) => scalars[i] = values.next(t).map_err(&err)?,
+                    Property::List(_, count_type, item_type) => {
+                        let n = values.next(count_type).map_err(&err)? as usize;
+                        let items = (0..n).map(|_| values.next(item_type)).collect::<Result<Vec<f64>, String>>();
+                        let items = items.map_err(&err)?;
+                        if Some(i) == face {
+                            list = items;
+                        }
+                    }
+                }
+            }
+            let get = |i: Option<usize>| scalars[i.unwrap()] as f32;
+            if e.name == "vertex" {
+                mesh.positions.push(Vec3::new(get(xyz[0]), get(xyz[1]), get(xyz[2])));
+                if normal.iter().all(|i| i.is_some()) {
+                    mesh.normals.push(Vec3::new(get(normal[0]), get(normal[1]), get(normal[2])));
+                }
+                if uv.iter().all(|i| i.is_some()) {
+                    mesh.uvs.push((get(uv[0]), get(uv[1])));
+                }
+                if rgb.iter().all(|i| i.is_some()) {
+                    let channel = |i: Option<usize>| {
+                        let scale = match props[i.unwrap()] {
+                            Property::Scalar(_, t) => t.color_scale(),
+                            Property::List(..) => 1.,
+                        };
+                        (scalars[i.unwrap()] * scale) as f32
+                    };
+                    mesh.colors.push(Vec3::new(channel(rgb[0]), channel(rgb[1]), channel(rgb[2])));
+                }
+            } else if e.name == "face" {
+                if list.len() < 3 {
+                    return Err(LoadError::parse(file,
+                                                values.position(),
+                                                format!("face needs 3 vertices, got {}", list.len())));
+                }
+                let vertices = elements.iter().find(|e| e.name == "vertex").map_or(0, |e| e.count);
+                if let Some(&bad) = list.iter().find(|&&i| i < 0. || i as usize >= vertices) {
+                    return Err(LoadError::parse(file,
+                                                values.position(),
+                                                format!("vertex index {} out of range ({} vertices)", bad, vertices)));
+                }
+                for k in 1..list.len() - 1 {
+                    mesh.indices.push([list[0] as u32, list[k] as u32, list[k + 1] as u32]);
+                }
+            }
+        }
+    }
+    if mesh.indices.is_empty() {
+        return Err(LoadError::parse(file, header_lines, "no faces"));
+    }
+    Ok(mesh)
+}
+
+/// reads a PLY file into mesh buffers, see `parse_ply`. wrap the result in
+/// `TriangleMesh::new` or, for colored scans, `TriangleMesh::with_vertex_colors`.
+pub fn load_ply(path: &Path) -> Result<MeshData, LoadError> {
+    let name = path.display().to_string();
+    let mut bytes = vec![];
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| LoadError::Io(name.clone(), e))?;
+    parse_ply(&bytes, &name)
+}
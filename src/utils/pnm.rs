@@ -0,0 +1,128 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use utils::load::LoadError;
+
+/// single channel image with values in `[0, 1]`, rows stored top to bottom
+#[derive(Clone, Debug)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<f32>,
+}
+
+/// splits the header into whitespace separated tokens, skipping `#` comments. returns the
+/// tokens with the line they start on and the offset just past the last one.
+fn header_tokens(bytes: &[u8], count: usize) -> (Vec<(String, usize)>, usize) {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    while tokens.len() < count && i < bytes.len() {
+        match bytes[i] {
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'\n' => {
+                line += 1;
+                i += 1;
+            }
+            c if c.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'#' {
+                    i += 1;
+                }
+                tokens.push((String::from_utf8_lossy(&bytes[start..i]).into_owned(), line));
+            }
+        }
+    }
+    (tokens, i)
+}
+
+/// parses ASCII (P2, P3) and binary (P5, P6) PGM and PPM images. colors are reduced to
+/// their Rec. 709 luminance, 16 bit binary samples are read big endian as the format requires.
+pub fn parse_pnm(bytes: &[u8], file: &str) -> Result<GrayImage, LoadError> {
+    let (tokens, end) = header_tokens(bytes, 4);
+    if tokens.len() < 4 {
+        return Err(LoadError::invalid(file, "truncated header"));
+    }
+    let (channels, binary) = match tokens[0].0.as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        m => return Err(LoadError::parse(file, 1, format!("unsupported magic number '{}'", m))),
+    };
+    let mut header = [0usize; 3];
+    for (k, &(ref t, line)) in tokens[1..].iter().enumerate() {
+        header[k] = match t.parse() {
+            Ok(v) if v > 0 => v,
+            _ => return Err(LoadError::parse(file, line, format!("invalid header value '{}'", t))),
+        };
+    }
+    let [width, height, maxval] = header;
+    if maxval > 65535 {
+        return Err(LoadError::parse(file, tokens[3].1, format!("maximum value {} above 65535", maxval)));
+    }
+    let count = match width.checked_mul(height).and_then(|n| n.checked_mul(channels)) {
+        Some(count) => count,
+        None => return Err(LoadError::invalid(file, format!("image size {}x{} is too large", width, height))),
+    };
+    let samples: Vec<f32> = if binary {
+        // exactly one whitespace character separates the header from the raster
+        let data = &bytes[(end + 1).min(bytes.len())..];
+        let size = if maxval > 255 { 2 } else { 1 };
+        let expected = match count.checked_mul(size) {
+            Some(expected) => expected,
+            None => return Err(LoadError::invalid(file, format!("image size {}x{} is too large", width, height))),
+        };
+        if data.len() < expected {
+            let message = format!("raster has {} bytes, expected {}", data.len(), expected);
+            return Err(LoadError::invalid(file, message));
+        }
+        (0..count)
+            .map(|i| if size == 2 {
+                u16::from_be_bytes([data[2 * i], data[2 * i + 1]]) as f32
+            } else {
+                data[i] as f32
+            })
+            .collect()
+    } else {
+        let (values, _) = header_tokens(&bytes[end..], count);
+        if values.len() < count {
+            return Err(LoadError::invalid(file, format!("raster has {} samples, expected {}", values.len(), count)));
+        }
+        let first_line = tokens[3].1 - 1;
+        values.iter()
+            .map(|&(ref t, line)| {
+                t.parse::<u32>()
+                    .map(|v| v as f32)
+                    .map_err(|_| LoadError::parse(file, first_line + line, format!("invalid sample '{}'", t)))
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let scale = 1. / maxval as f32;
+    let pixels = if channels == 1 {
+        samples.iter().map(|&s| (s * scale).min(1.)).collect()
+    } else {
+        samples.chunks(3)
+            .map(|c| ((0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]) * scale).min(1.))
+            .collect()
+    };
+    Ok(GrayImage {
+        width: width,
+        height: height,
+        pixels: pixels,
+    })
+}
+
+pub fn load_pnm(path: &Path) -> Result<GrayImage, LoadError> {
+    let name = path.display().to_string();
+    let mut bytes = vec![];
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| LoadError::Io(name.clone(), e))?;
+    parse_pnm(&bytes, &name)
+}
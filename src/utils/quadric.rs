@@ -0,0 +1,423 @@
+use std::f32::consts::PI;
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::{unit_vector, Vec3};
+use utils::ray::Ray;
+use utils::material::Material;
+use utils::aabb::AABB;
+
+// the quadrics are built around the +y axis from `center`, with the azimuth `phi` measured
+// from +x towards +z. `phi_max` is given in degrees and cuts the surface to a partial sweep,
+// `u` runs along the sweep and `v` along the height (or radius, for disks and caps).
+
+/// one point where a ray crosses a surface, with the normal in local space
+struct Crossing {
+    t: f32,
+    u: f32,
+    v: f32,
+    normal: Vec3,
+}
+
+/// real roots of `a t^2 + b t + c` in ascending order, solved in double precision
+/// without the cancellation of the textbook formula. a double root is returned twice,
+/// a linear equation has a single root.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0. {
+        if b == 0. {
+            return vec![];
+        }
+        return vec![-c / b];
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return vec![];
+    }
+    let q = if b < 0. {
+        -0.5 * (b - discriminant.sqrt())
+    } else {
+        -0.5 * (b + discriminant.sqrt())
+    };
+    let (t0, t1) = if q == 0. {
+        (0., 0.)
+    } else {
+        (q / a, c / q)
+    };
+    if t0 > t1 {
+        vec![t1, t0]
+    } else {
+        vec![t0, t1]
+    }
+}
+
+fn azimuth(x: f32, z: f32) -> f32 {
+    let phi = z.atan2(x);
+    if phi < 0. {
+        phi + 2. * PI
+    } else {
+        phi
+    }
+}
+
+/// crossing with the annulus `r_inner <= dist <= r_outer` of the plane at height `y`
+fn cap(o: &Vec3, d: &Vec3, y: f32, r_outer: f32, r_inner: f32, phi_max: f32, up: bool) -> Option<Crossing> {
+    if d.y() == 0. {
+        return None;
+    }
+    let t = (y - o.y()) / d.y();
+    let x = o.x() + t * d.x();
+    let z = o.z() + t * d.z();
+    let dist2 = x * x + z * z;
+    if dist2 > r_outer * r_outer || dist2 < r_inner * r_inner {
+        return None;
+    }
+    let phi = azimuth(x, z);
+    if phi > phi_max {
+        return None;
+    }
+    Some(Crossing {
+        t: t,
+        u: phi / phi_max,
+        v: (r_outer - dist2.sqrt()) / (r_outer - r_inner),
+        normal: Vec3::new(0., if up { 1. } else { -1. }, 0.),
+    })
+}
+
+/// side surface `a t^2 + b t + c = 0` of a quadric between heights `y0` and `y1`,
+/// where the coefficients come from the ray
+struct Side {
+    coefficients: (f64, f64, f64),
+    y0: f32,
+    y1: f32,
+    phi_max: f32,
+}
+
+impl Side {
+    /// `surface` maps the local hit point to `(v, unnormalized normal)`
+    fn crossings<F>(&self, o: &Vec3, d: &Vec3, surface: F, crossings: &mut Vec<Crossing>)
+        where F: Fn(&Vec3) -> (f32, Vec3)
+    {
+        let (a, b, c) = self.coefficients;
+        for t in solve_quadratic(a, b, c) {
+            let t = t as f32;
+            let p = o.clone() + d.clone() * t;
+            if p.y() < self.y0 || p.y() > self.y1 {
+                continue;
+            }
+            let phi = azimuth(p.x(), p.z());
+            if phi > self.phi_max {
+                continue;
+            }
+            let (v, n) = surface(&p);
+            crossings.push(Crossing {
+                t: t,
+                u: phi / self.phi_max,
+                v: v,
+                normal: unit_vector(n),
+            });
+        }
+    }
+}
+
+fn hit_closest(crossings: Vec<Crossing>,
+               r: &Ray,
+               t_min: f32,
+               t_max: f32,
+               mat: &Material,
+               rec: &mut HitRecord)
+               -> bool {
+    let mut closest: Option<Crossing> = None;
+    for c in crossings.into_iter() {
+        if c.t > t_min && c.t < t_max && closest.as_ref().is_none_or(|b| c.t < b.t) {
+            closest = Some(c);
+        }
+    }
+    match closest {
+        Some(c) => {
+            rec.t = c.t;
+            rec.u = c.u;
+            rec.v = c.v;
+            rec.p = r.point_at_parameter(c.t);
+            rec.normal = c.normal;
+            rec.mat = mat.box_clone();
+            true
+        }
+        None => false,
+    }
+}
+
+fn to_records(crossings: Vec<Crossing>,
+              r: &Ray,
+              t_min: f32,
+              t_max: f32,
+              mat: &Material)
+              -> Vec<HitRecord> {
+    let mut records: Vec<HitRecord> = crossings.into_iter()
+        .filter(|c| c.t > t_min && c.t < t_max)
+        .map(|c| {
+            let mut rec = HitRecord::new(mat.box_clone());
+            rec.t = c.t;
+            rec.u = c.u;
+            rec.v = c.v;
+            rec.p = r.point_at_parameter(c.t);
+            rec.normal = c.normal;
+            rec
+        })
+        .collect();
+    records.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+    records
+}
+
+/// bounds of the full sweep, partial sweeps are not tightened
+fn sweep_bounds(center: &Vec3, radius: f32, y0: f32, y1: f32) -> AABB {
+    AABB::new(center.clone() + Vec3::new(-radius, y0, -radius),
+              center.clone() + Vec3::new(radius, y1, radius))
+}
+
+/// cylinder of the given radius from `center` up to `center.y + height`, optionally
+/// closed by disks at both ends
+#[derive(Clone)]
+pub struct Cylinder {
+    center: Vec3,
+    radius: f32,
+    height: f32,
+    phi_max: f32,
+    capped: bool,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl Cylinder {
+    pub fn new(center: Vec3, radius: f32, height: f32, phi_max: f32, capped: bool, m: Box<Material>) -> Self {
+        Self {
+            center: center,
+            radius: radius,
+            height: height,
+            phi_max: phi_max.clamp(0., 360.) * PI / 180.,
+            capped: capped,
+            mat: m,
+        }
+    }
+
+    fn surface_crossings(&self, r: &Ray) -> Vec<Crossing> {
+        let o = r.origin().clone() - self.center.clone();
+        let d = r.direction();
+        let (ox, oz, dx, dz) = (o.x() as f64, o.z() as f64, d.x() as f64, d.z() as f64);
+        let rr = self.radius as f64;
+        let mut crossings = Vec::with_capacity(4);
+        let h = self.height;
+        let side = Side {
+            coefficients: (dx * dx + dz * dz, 2. * (ox * dx + oz * dz), ox * ox + oz * oz - rr * rr),
+            y0: 0.,
+            y1: h,
+            phi_max: self.phi_max,
+        };
+        side.crossings(&o, d, |p| (p.y() / h, Vec3::new(p.x(), 0., p.z())), &mut crossings);
+        if self.capped {
+            crossings.extend(cap(&o, d, 0., self.radius, 0., self.phi_max, false));
+            crossings.extend(cap(&o, d, h, self.radius, 0., self.phi_max, true));
+        }
+        crossings
+    }
+}
+
+impl Hitable for Cylinder {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        hit_closest(self.surface_crossings(r), r, t_min, t_max, &*self.mat, rec)
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        to_records(self.surface_crossings(r), r, t_min, t_max, &*self.mat)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = sweep_bounds(&self.center, self.radius, 0., self.height);
+        true
+    }
+}
+
+/// cone with its base of the given radius at `center` and its apex at `center.y + height`,
+/// optionally closed by a disk at the base
+#[derive(Clone)]
+pub struct Cone {
+    center: Vec3,
+    radius: f32,
+    height: f32,
+    phi_max: f32,
+    capped: bool,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl Cone {
+    pub fn new(center: Vec3, radius: f32, height: f32, phi_max: f32, capped: bool, m: Box<Material>) -> Self {
+        Self {
+            center: center,
+            radius: radius,
+            height: height,
+            phi_max: phi_max.clamp(0., 360.) * PI / 180.,
+            capped: capped,
+            mat: m,
+        }
+    }
+
+    fn surface_crossings(&self, r: &Ray) -> Vec<Crossing> {
+        let o = r.origin().clone() - self.center.clone();
+        let d = r.direction();
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let (dx, dy, dz) = (d.x() as f64, d.y() as f64, d.z() as f64);
+        // x^2 + z^2 = k^2 (h - y)^2
+        let h = self.height;
+        let k = (self.radius / h) as f64;
+        let k2 = k * k;
+        let hy = h as f64 - oy;
+        let mut crossings = Vec::with_capacity(3);
+        let side = Side {
+            coefficients: (dx * dx + dz * dz - k2 * dy * dy,
+                           2. * (ox * dx + oz * dz + k2 * hy * dy),
+                           ox * ox + oz * oz - k2 * hy * hy),
+            y0: 0.,
+            y1: h,
+            phi_max: self.phi_max,
+        };
+        side.crossings(&o,
+                       d,
+                       |p| (p.y() / h, Vec3::new(p.x(), (k2 as f32) * (h - p.y()), p.z())),
+                       &mut crossings);
+        if self.capped {
+            crossings.extend(cap(&o, d, 0., self.radius, 0., self.phi_max, false));
+        }
+        crossings
+    }
+}
+
+impl Hitable for Cone {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        hit_closest(self.surface_crossings(r), r, t_min, t_max, &*self.mat, rec)
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        to_records(self.surface_crossings(r), r, t_min, t_max, &*self.mat)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = sweep_bounds(&self.center, self.radius, 0., self.height);
+        true
+    }
+}
+
+/// paraboloid `x^2 + z^2 = radius^2 * y / height` with its vertex at `center`, optionally
+/// closed by a disk at the top
+#[derive(Clone)]
+pub struct Paraboloid {
+    center: Vec3,
+    radius: f32,
+    height: f32,
+    phi_max: f32,
+    capped: bool,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl Paraboloid {
+    pub fn new(center: Vec3, radius: f32, height: f32, phi_max: f32, capped: bool, m: Box<Material>) -> Self {
+        Self {
+            center: center,
+            radius: radius,
+            height: height,
+            phi_max: phi_max.clamp(0., 360.) * PI / 180.,
+            capped: capped,
+            mat: m,
+        }
+    }
+
+    fn surface_crossings(&self, r: &Ray) -> Vec<Crossing> {
+        let o = r.origin().clone() - self.center.clone();
+        let d = r.direction();
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let (dx, dy, dz) = (d.x() as f64, d.y() as f64, d.z() as f64);
+        let h = self.height;
+        let k = (self.radius * self.radius / h) as f64;
+        let mut crossings = Vec::with_capacity(3);
+        let side = Side {
+            coefficients: (dx * dx + dz * dz, 2. * (ox * dx + oz * dz) - k * dy, ox * ox + oz * oz - k * oy),
+            y0: 0.,
+            y1: h,
+            phi_max: self.phi_max,
+        };
+        side.crossings(&o, d, |p| (p.y() / h, Vec3::new(2. * p.x(), -(k as f32), 2. * p.z())), &mut crossings);
+        if self.capped {
+            crossings.extend(cap(&o, d, h, self.radius, 0., self.phi_max, true));
+        }
+        crossings
+    }
+}
+
+impl Hitable for Paraboloid {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        hit_closest(self.surface_crossings(r), r, t_min, t_max, &*self.mat, rec)
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        to_records(self.surface_crossings(r), r, t_min, t_max, &*self.mat)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = sweep_bounds(&self.center, self.radius, 0., self.height);
+        true
+    }
+}
+
+/// flat disk, or annulus when `inner_radius > 0`, lying in the plane `y = center.y`
+/// and facing +y
+#[derive(Clone)]
+pub struct Disk {
+    center: Vec3,
+    radius: f32,
+    inner_radius: f32,
+    phi_max: f32,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl Disk {
+    pub fn new(center: Vec3, radius: f32, inner_radius: f32, phi_max: f32, m: Box<Material>) -> Self {
+        Self {
+            center: center,
+            radius: radius,
+            inner_radius: inner_radius,
+            phi_max: phi_max.clamp(0., 360.) * PI / 180.,
+            mat: m,
+        }
+    }
+
+    fn surface_crossings(&self, r: &Ray) -> Vec<Crossing> {
+        let o = r.origin().clone() - self.center.clone();
+        cap(&o, r.direction(), 0., self.radius, self.inner_radius, self.phi_max, true).into_iter().collect()
+    }
+}
+
+impl Hitable for Disk {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        hit_closest(self.surface_crossings(r), r, t_min, t_max, &*self.mat, rec)
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        to_records(self.surface_crossings(r), r, t_min, t_max, &*self.mat)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = sweep_bounds(&self.center, self.radius, -0.0001, 0.0001);
+        true
+    }
+}
@@ -1,8 +1,17 @@
 extern crate rand;
 
-use self::rand::{thread_rng, Rng};
+use std::cell::RefCell;
+use self::rand::{thread_rng, Rng, SeedableRng, XorShiftRng};
+
+thread_local! {
+    static RNG: RefCell<XorShiftRng> = RefCell::new(thread_rng().gen());
+}
+
+/// restarts the numbers `drand48` returns on this thread from `s`, so that tests are repeatable
+pub fn seed(s: u32) {
+    RNG.with(|rng| *rng.borrow_mut() = XorShiftRng::from_seed([s, 0x9e37_79b9, 0x7f4a_7c15, 1]));
+}
 
 pub fn drand48() -> f32 {
-    let mut rng = thread_rng();
-    rng.gen_range(0., 1.)
+    RNG.with(|rng| rng.borrow_mut().gen_range(0., 1.))
 }
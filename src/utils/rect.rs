@@ -31,7 +31,8 @@ impl XYRect {
 impl Hitable for XYRect {
     fn hit(&self, r: &Ray, t0: f32, t1: f32, rec: &mut HitRecord) -> bool {
         let t = (self.k - r.origin().z()) / r.direction().z();
-        if t < t0 || t > t1 {
+        // a ray in the plane of the rectangle gives 0 / 0
+        if t.is_nan() || t < t0 || t > t1 {
             return false;
         }
         let x = r.origin().x() + t * r.direction().x();
@@ -55,3 +56,109 @@ impl Hitable for XYRect {
         Box::new((*self).clone())
     }
 }
+
+#[derive(Clone)]
+pub struct XZRect {
+    x0: f32,
+    x1: f32,
+    z0: f32,
+    z1: f32,
+    k: f32,
+    mp: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl XZRect {
+    pub fn new(x0: f32, x1: f32, z0: f32, z1: f32, k: f32, mt: Box<Material>) -> Self {
+        Self {
+            x0: x0,
+            x1: x1,
+            z0: z0,
+            z1: z1,
+            k: k,
+            mp: mt,
+        }
+    }
+}
+
+impl Hitable for XZRect {
+    fn hit(&self, r: &Ray, t0: f32, t1: f32, rec: &mut HitRecord) -> bool {
+        let t = (self.k - r.origin().y()) / r.direction().y();
+        // a ray in the plane of the rectangle gives 0 / 0
+        if t.is_nan() || t < t0 || t > t1 {
+            return false;
+        }
+        let x = r.origin().x() + t * r.direction().x();
+        let z = r.origin().z() + t * r.direction().z();
+        if x < self.x0 || x > self.x1 || z < self.z0 || z > self.z1 {
+            return false;
+        }
+        rec.u = (x - self.x0) / (self.x1 - self.x0);
+        rec.v = (z - self.z0) / (self.z1 - self.z0);
+        rec.t = t;
+        rec.mat = self.mp.clone();
+        rec.p = r.point_at_parameter(t);
+        rec.normal = Vec3::new(0., 1., 0.);
+        true
+    }
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = AABB::new(Vec3::new(self.x0, self.k - 0.0001, self.z0), Vec3::new(self.x1, self.k + 0.0001, self.z1));
+        true
+    }
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+}
+
+#[derive(Clone)]
+pub struct YZRect {
+    y0: f32,
+    y1: f32,
+    z0: f32,
+    z1: f32,
+    k: f32,
+    mp: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl YZRect {
+    pub fn new(y0: f32, y1: f32, z0: f32, z1: f32, k: f32, mt: Box<Material>) -> Self {
+        Self {
+            y0: y0,
+            y1: y1,
+            z0: z0,
+            z1: z1,
+            k: k,
+            mp: mt,
+        }
+    }
+}
+
+impl Hitable for YZRect {
+    fn hit(&self, r: &Ray, t0: f32, t1: f32, rec: &mut HitRecord) -> bool {
+        let t = (self.k - r.origin().x()) / r.direction().x();
+        // a ray in the plane of the rectangle gives 0 / 0
+        if t.is_nan() || t < t0 || t > t1 {
+            return false;
+        }
+        let y = r.origin().y() + t * r.direction().y();
+        let z = r.origin().z() + t * r.direction().z();
+        if y < self.y0 || y > self.y1 || z < self.z0 || z > self.z1 {
+            return false;
+        }
+        rec.u = (y - self.y0) / (self.y1 - self.y0);
+        rec.v = (z - self.z0) / (self.z1 - self.z0);
+        rec.t = t;
+        rec.mat = self.mp.clone();
+        rec.p = r.point_at_parameter(t);
+        rec.normal = Vec3::new(1., 0., 0.);
+        true
+    }
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = AABB::new(Vec3::new(self.k - 0.0001, self.y0, self.z0), Vec3::new(self.k + 0.0001, self.y1, self.z1));
+        true
+    }
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+}
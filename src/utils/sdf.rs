@@ -0,0 +1,386 @@
+use std::sync::Arc;
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::{unit_vector, Vec3};
+use utils::ray::Ray;
+use utils::material::Material;
+use utils::aabb::AABB;
+
+const MAX_MARCH_STEPS: usize = 512;
+const HIT_EPSILON: f32 = 0.0001;
+const NORMAL_EPSILON: f32 = 0.0001;
+const ROOT_BISECTIONS: usize = 24;
+
+/// signed distance to a surface, negative inside. `lipschitz` bounds how much faster than
+/// the true distance the value can change; sphere tracing divides its steps by it.
+pub trait Sdf {
+    fn distance(&self, p: &Vec3) -> f32;
+    fn lipschitz(&self) -> f32 {
+        1.
+    }
+    fn box_clone(&self) -> Box<Sdf>;
+}
+
+impl Clone for Box<Sdf> {
+    fn clone(&self) -> Box<Sdf> {
+        self.box_clone()
+    }
+}
+
+fn length(x: f32, y: f32, z: f32) -> f32 {
+    (x * x + y * y + z * z).sqrt()
+}
+
+/// wraps a closure as a distance field. clones share the closure.
+pub struct SdfFn<F> {
+    f: Arc<F>,
+    lipschitz: f32,
+}
+
+impl<F> Clone for SdfFn<F> {
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            lipschitz: self.lipschitz,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl<F: Fn(&Vec3) -> f32 + 'static> SdfFn<F> {
+    pub fn new(f: F, lipschitz: f32) -> Self {
+        Self {
+            f: Arc::new(f),
+            lipschitz: lipschitz,
+        }
+    }
+}
+
+impl<F: Fn(&Vec3) -> f32 + 'static> Sdf for SdfFn<F> {
+    fn distance(&self, p: &Vec3) -> f32 {
+        (self.f)(p)
+    }
+    fn lipschitz(&self) -> f32 {
+        self.lipschitz
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+#[derive(Clone)]
+pub struct SdfSphere {
+    center: Vec3,
+    radius: f32,
+}
+
+#[allow(dead_code)]
+impl SdfSphere {
+    pub fn new(cen: Vec3, r: f32) -> Self {
+        Self {
+            center: cen,
+            radius: r,
+        }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Vec3) -> f32 {
+        (p.clone() - self.center.clone()).len() - self.radius
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+/// box with the given half extents, its edges rounded off by `radius`
+#[derive(Clone)]
+pub struct SdfRoundBox {
+    center: Vec3,
+    half: Vec3,
+    radius: f32,
+}
+
+#[allow(dead_code)]
+impl SdfRoundBox {
+    pub fn new(cen: Vec3, half: Vec3, radius: f32) -> Self {
+        Self {
+            center: cen,
+            half: half,
+            radius: radius,
+        }
+    }
+}
+
+impl Sdf for SdfRoundBox {
+    fn distance(&self, p: &Vec3) -> f32 {
+        let q: Vec<f32> = (0..3)
+            .map(|a| (p.e[a] - self.center.e[a]).abs() - (self.half.e[a] - self.radius))
+            .collect();
+        let outside = length(q[0].max(0.), q[1].max(0.), q[2].max(0.));
+        let inside = q[0].max(q[1]).max(q[2]).min(0.);
+        outside + inside - self.radius
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+/// box with sharp edges, a rounded box with zero radius
+#[derive(Clone)]
+pub struct SdfBox {
+    inner: SdfRoundBox,
+}
+
+#[allow(dead_code)]
+impl SdfBox {
+    pub fn new(cen: Vec3, half: Vec3) -> Self {
+        Self { inner: SdfRoundBox::new(cen, half, 0.) }
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self.inner.distance(p)
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+/// torus around the y axis, as `utils::torus::Torus`
+#[derive(Clone)]
+pub struct SdfTorus {
+    center: Vec3,
+    major_radius: f32,
+    minor_radius: f32,
+}
+
+#[allow(dead_code)]
+impl SdfTorus {
+    pub fn new(cen: Vec3, major_radius: f32, minor_radius: f32) -> Self {
+        Self {
+            center: cen,
+            major_radius: major_radius,
+            minor_radius: minor_radius,
+        }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Vec3) -> f32 {
+        let q = p.clone() - self.center.clone();
+        let ring = (q.x() * q.x() + q.z() * q.z()).sqrt() - self.major_radius;
+        (ring * ring + q.y() * q.y()).sqrt() - self.minor_radius
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+/// union that blends the two fields within distance `k` of where they meet
+#[derive(Clone)]
+pub struct SmoothUnion {
+    a: Box<Sdf>,
+    b: Box<Sdf>,
+    k: f32,
+}
+
+#[allow(dead_code)]
+impl SmoothUnion {
+    pub fn new(a: Box<Sdf>, b: Box<Sdf>, k: f32) -> Self {
+        Self { a: a, b: b, k: k }
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: &Vec3) -> f32 {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0. {
+            return da.min(db);
+        }
+        let h = (0.5 + 0.5 * (db - da) / self.k).clamp(0., 1.);
+        db + (da - db) * h - self.k * h * (1. - h)
+    }
+    fn lipschitz(&self) -> f32 {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+/// rotates the xz plane by `rate` radians per unit of height. twisting stretches the field,
+/// the more the further from the y axis, so `max_radius` must bound how far the shape reaches.
+#[derive(Clone)]
+pub struct Twist {
+    inner: Box<Sdf>,
+    rate: f32,
+    max_radius: f32,
+}
+
+#[allow(dead_code)]
+impl Twist {
+    pub fn new(inner: Box<Sdf>, rate: f32, max_radius: f32) -> Self {
+        Self {
+            inner: inner,
+            rate: rate,
+            max_radius: max_radius,
+        }
+    }
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: &Vec3) -> f32 {
+        let (s, c) = (self.rate * p.y()).sin_cos();
+        self.inner.distance(&Vec3::new(c * p.x() - s * p.z(), p.y(), s * p.x() + c * p.z()))
+    }
+    fn lipschitz(&self) -> f32 {
+        let shear = self.rate * self.max_radius;
+        self.inner.lipschitz() * (1. + shear * shear).sqrt()
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+/// repeats the field every `period` along each axis, a zero component leaves that axis alone.
+/// the shape must fit into one cell around the origin.
+#[derive(Clone)]
+pub struct Repeat {
+    inner: Box<Sdf>,
+    period: Vec3,
+}
+
+#[allow(dead_code)]
+impl Repeat {
+    pub fn new(inner: Box<Sdf>, period: Vec3) -> Self {
+        Self {
+            inner: inner,
+            period: period,
+        }
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: &Vec3) -> f32 {
+        let mut q = p.clone();
+        for a in 0..3 {
+            let period = self.period.e[a];
+            if period > 0. {
+                q.e[a] -= period * (q.e[a] / period).round();
+            }
+        }
+        self.inner.distance(&q)
+    }
+    fn lipschitz(&self) -> f32 {
+        self.inner.lipschitz()
+    }
+    fn box_clone(&self) -> Box<Sdf> {
+        Box::new((*self).clone())
+    }
+}
+
+/// surface where a distance field is zero, found by sphere tracing inside `bounds`, which
+/// must enclose it. normals are central differences of the field, `u` and `v` project the
+/// hit point onto the xz plane of `bounds`.
+#[derive(Clone)]
+pub struct SdfHitable {
+    sdf: Box<Sdf>,
+    bounds: AABB,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl SdfHitable {
+    pub fn new(sdf: Box<Sdf>, bounds: AABB, m: Box<Material>) -> Self {
+        Self {
+            sdf: sdf,
+            bounds: bounds,
+            mat: m,
+        }
+    }
+
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        let mut n = Vec3::new(0., 0., 0.);
+        for a in 0..3 {
+            let (mut hi, mut lo) = (p.clone(), p.clone());
+            hi.e[a] += NORMAL_EPSILON;
+            lo.e[a] -= NORMAL_EPSILON;
+            n.e[a] = self.sdf.distance(&hi) - self.sdf.distance(&lo);
+        }
+        if n.squared_len() > 0. {
+            unit_vector(n)
+        } else {
+            Vec3::new(0., 1., 0.)
+        }
+    }
+
+    /// marches from `t0` to `t1` with steps of the absolute distance, so rays that start
+    /// inside find their way out. a change of sign between two samples is bisected.
+    fn march(&self, r: &Ray, t0: f32, t1: f32) -> Option<f32> {
+        let len = r.direction().len();
+        if len == 0. {
+            return None;
+        }
+        let scale = 1. / (len * self.sdf.lipschitz());
+        let field = |t: f32| self.sdf.distance(&r.point_at_parameter(t));
+        let mut t = t0;
+        let mut d = field(t);
+        let outside = d >= 0.;
+        for step in 0..MAX_MARCH_STEPS {
+            // the starting point may sit on the surface it just left, never stop there
+            if step > 0 && d.abs() < HIT_EPSILON {
+                return Some(t);
+            }
+            let next = t + d.abs().max(HIT_EPSILON) * scale;
+            if next >= t1 {
+                return None;
+            }
+            let next_d = field(next);
+            if (next_d >= 0.) != outside {
+                let (mut lo, mut hi) = (t, next);
+                for _ in 0..ROOT_BISECTIONS {
+                    let mid = 0.5 * (lo + hi);
+                    if (field(mid) >= 0.) == outside {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                return Some(hi);
+            }
+            t = next;
+            d = next_d;
+        }
+        None
+    }
+}
+
+impl Hitable for SdfHitable {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let (t0, t1) = match self.bounds.clip(r, t_min, t_max) {
+            Some(span) => span,
+            None => return false,
+        };
+        let t = match self.march(r, t0, t1) {
+            Some(t) if t > t_min => t,
+            _ => return false,
+        };
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.normal = self.normal(&rec.p);
+        let (min, max) = (self.bounds.clone().min(), self.bounds.clone().max());
+        rec.u = (rec.p.x() - min.x()) / (max.x() - min.x());
+        rec.v = (rec.p.z() - min.z()) / (max.z() - min.z());
+        rec.mat = self.mat.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = self.bounds.clone();
+        true
+    }
+}
@@ -3,7 +3,7 @@ use utils::ray::Ray;
 use utils::vec3::{dot, Vec3};
 use utils::random::drand48;
 use utils::material::Material;
-use utils::aabb::AABB;
+use utils::aabb::{AABB, surrounding_box};
 
 #[derive(Clone)]
 pub struct Sphere {
@@ -53,9 +53,31 @@ impl Hitable for Sphere {
         return false;
     }
 
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let oc: Vec3 = r.origin().clone() - self.center.clone();
+        let a: f32 = dot(r.direction(), r.direction());
+        let b: f32 = dot(&oc, r.direction());
+        let c: f32 = dot(&oc, &oc) - self.radius * self.radius;
+        let discriminaun = b * b - a * c;
+        if discriminaun <= 0. {
+            return vec![];
+        }
+        [(-b - discriminaun.sqrt()) / a, (-b + discriminaun.sqrt()) / a]
+            .iter()
+            .filter(|&&t| t > t_min && t < t_max)
+            .map(|&t| {
+                let mut rec = HitRecord::new(self.mat.clone());
+                rec.t = t;
+                rec.p = r.point_at_parameter(t);
+                rec.normal = (rec.p.clone() - self.center.clone()) / self.radius;
+                rec
+            })
+            .collect()
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
-        *vox = AABB::new(Vec3::new(self.radius, self.radius, self.radius) - self.center.clone(),
-                         Vec3::new(self.radius, self.radius, self.radius));
+        *vox = AABB::new(self.center.clone() - Vec3::new(self.radius, self.radius, self.radius),
+                         self.center.clone() + Vec3::new(self.radius, self.radius, self.radius));
         true
     }
 }
@@ -122,9 +144,12 @@ impl Hitable for MovingSphere {
         return false;
     }
 
-    fn bounding_box(&self, _t0: f32, _t1: f32, _vox: &mut AABB) -> bool {
-        // not implemented
-        false
+    fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = AABB::new(self.center(t0) - rvec.clone(), self.center(t0) + rvec.clone());
+        let box1 = AABB::new(self.center(t1) - rvec.clone(), self.center(t1) + rvec);
+        *vox = surrounding_box(box0, box1);
+        true
     }
 }
 
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use utils::vec3::{cross, unit_vector, Vec3};
+use utils::mesh::MeshData;
+use utils::load::LoadError;
+
+#[derive(Clone, Copy, Debug)]
+pub struct StlOptions {
+    /// factor applied to every coordinate, e.g. 0.001 for parts modelled in millimetres
+    pub scale: f32,
+    /// merge vertices closer than this distance and give them averaged normals for smooth
+    /// shading. `None` keeps every facet separate and flat shaded.
+    pub weld: Option<f32>,
+}
+
+impl Default for StlOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            weld: None,
+        }
+    }
+}
+
+/// facet count stored in the header of a binary STL, which must be at least 84 bytes long
+fn facet_count(bytes: &[u8]) -> usize {
+    u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize
+}
+
+/// size of a binary STL holding `count` facets, `None` if that overflows
+fn binary_size(count: usize) -> Option<usize> {
+    count.checked_mul(50).and_then(|n| n.checked_add(84))
+}
+
+fn parse_binary(bytes: &[u8], file: &str) -> Result<Vec<[Vec3; 3]>, LoadError> {
+    if bytes.len() < 84 {
+        return Err(LoadError::invalid(file, "file too short for a binary STL header"));
+    }
+    let count = facet_count(bytes);
+    // check the size before trusting the count with an allocation
+    match binary_size(count) {
+        Some(size) if size <= bytes.len() => {}
+        _ => {
+            let message = format!("unexpected end of file, header announces {} facets", count);
+            return Err(LoadError::invalid(file, message));
+        }
+    }
+    let f = |o: usize| f32::from_le_bytes([bytes[o], bytes[o + 1], bytes[o + 2], bytes[o + 3]]);
+    let mut facets = Vec::with_capacity(count);
+    for i in 0..count {
+        // 12 bytes of normal, three 12 byte vertices and a 2 byte attribute count
+        let o = 84 + 50 * i;
+        let v = |k: usize| Vec3::new(f(o + 12 * k), f(o + 12 * k + 4), f(o + 12 * k + 8));
+        facets.push([v(1), v(2), v(3)]);
+    }
+    Ok(facets)
+}
+
+fn parse_ascii(text: &str, file: &str) -> Result<Vec<[Vec3; 3]>, LoadError> {
+    let mut facets = vec![];
+    let mut vertices: Vec<Vec3> = vec![];
+    let mut in_loop = false;
+    for (n, l) in text.lines().enumerate() {
+        let line = n + 1;
+        let tokens: Vec<&str> = l.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        match tokens[0] {
+            "outer" => {
+                if in_loop {
+                    return Err(LoadError::parse(file, line, "nested 'outer loop'"));
+                }
+                in_loop = true;
+                vertices.clear();
+            }
+            "vertex" => {
+                if !in_loop {
+                    return Err(LoadError::parse(file, line, "'vertex' outside of 'outer loop'"));
+                }
+                if tokens.len() != 4 {
+                    return Err(LoadError::parse(file, line, "'vertex' needs 3 numbers"));
+                }
+                let mut c = [0f32; 3];
+                for (i, t) in tokens[1..].iter().enumerate() {
+                    c[i] = t.parse().map_err(|_| LoadError::parse(file, line, format!("invalid number '{}'", t)))?;
+                }
+                vertices.push(Vec3::new(c[0], c[1], c[2]));
+            }
+            "endloop" => {
+                if vertices.len() != 3 {
+                    let message = format!("facet has {} vertices, expected 3", vertices.len());
+                    return Err(LoadError::parse(file, line, message));
+                }
+                in_loop = false;
+                facets.push([vertices[0].clone(), vertices[1].clone(), vertices[2].clone()]);
+            }
+            "solid" | "facet" | "endfacet" | "endsolid" => {}
+            k => return Err(LoadError::parse(file, line, format!("unknown keyword '{}'", k))),
+        }
+    }
+    if in_loop {
+        return Err(LoadError::parse(file, text.lines().count(), "missing 'endloop'"));
+    }
+    Ok(facets)
+}
+
+fn build_mesh(facets: Vec<[Vec3; 3]>, options: &StlOptions) -> MeshData {
+    let mut mesh = MeshData::default();
+    let tolerance = match options.weld {
+        Some(t) => t,
+        None => {
+            for f in facets.into_iter() {
+                let i = mesh.positions.len() as u32;
+                mesh.positions.extend(f.iter().map(|v| v.clone() * options.scale));
+                mesh.indices.push([i, i + 1, i + 2]);
+            }
+            return mesh;
+        }
+    };
+
+    // bucket vertices into `tolerance` sized cells, so any vertex within `tolerance` of a
+    // new one sits in the same or a neighbouring cell, then average area weighted face normals
+    let cell = tolerance.max(1e-6);
+    let limit = tolerance * tolerance;
+    let mut lookup: HashMap<(i64, i64, i64), Vec<u32>> = HashMap::new();
+    let mut accum: Vec<Vec3> = vec![];
+    for f in facets.into_iter() {
+        let mut idx = [0u32; 3];
+        for (k, v) in f.iter().enumerate() {
+            let p = v.clone() * options.scale;
+            let key = ((p.x() / cell).floor() as i64, (p.y() / cell).floor() as i64, (p.z() / cell).floor() as i64);
+            let mut found = None;
+            'search: for dx in -1..2 {
+                for dy in -1..2 {
+                    for dz in -1..2 {
+                        let near = match lookup.get(&(key.0 + dx, key.1 + dy, key.2 + dz)) {
+                            Some(near) => near,
+                            None => continue,
+                        };
+                        for &i in near.iter() {
+                            if (mesh.positions[i as usize].clone() - p.clone()).squared_len() <= limit {
+                                found = Some(i);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            idx[k] = match found {
+                Some(i) => i,
+                None => {
+                    let next = mesh.positions.len() as u32;
+                    lookup.entry(key).or_default().push(next);
+                    mesh.positions.push(p);
+                    accum.push(Vec3::new(0., 0., 0.));
+                    next
+                }
+            };
+        }
+        if idx[0] == idx[1] || idx[1] == idx[2] || idx[0] == idx[2] {
+            // collapsed by welding
+            continue;
+        }
+        let a = mesh.positions[idx[0] as usize].clone();
+        let b = mesh.positions[idx[1] as usize].clone();
+        let c = mesh.positions[idx[2] as usize].clone();
+        let n = cross(&(b - a.clone()), &(c - a));
+        for &i in idx.iter() {
+            accum[i as usize] = accum[i as usize].clone() + n.clone();
+        }
+        mesh.indices.push(idx);
+    }
+    mesh.normals = accum.into_iter()
+        .map(|n| if n.squared_len() > 0. {
+            unit_vector(n)
+        } else {
+            Vec3::new(0., 0., 1.)
+        })
+        .collect();
+    mesh
+}
+
+/// parses binary or ASCII STL. without welding every facet gets its own three vertices and is
+/// shaded with its face normal, which is taken from the winding rather than the stored normal.
+pub fn parse_stl(bytes: &[u8], file: &str, options: &StlOptions) -> Result<MeshData, LoadError> {
+    // ASCII files start with "solid", but so do the headers of some binary exporters,
+    // so trust the facet count when it matches the file size exactly
+    let exact_binary = bytes.len() >= 84 && binary_size(facet_count(bytes)) == Some(bytes.len());
+    let facets = if !bytes.starts_with(b"solid") || exact_binary {
+        parse_binary(bytes, file)?
+    } else {
+        let text = ::std::str::from_utf8(bytes)
+            .map_err(|_| LoadError::invalid(file, "ASCII STL is not valid UTF-8"))?;
+        parse_ascii(text, file)?
+    };
+    if facets.is_empty() {
+        return Err(LoadError::invalid(file, "no facets"));
+    }
+    let mesh = build_mesh(facets, options);
+    if mesh.indices.is_empty() {
+        return Err(LoadError::invalid(file, "every facet collapsed when welding"));
+    }
+    Ok(mesh)
+}
+
+pub fn load_stl(path: &Path, options: &StlOptions) -> Result<MeshData, LoadError> {
+    let name = path.display().to_string();
+    let mut bytes = vec![];
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| LoadError::Io(name.clone(), e))?;
+    parse_stl(&bytes, &name, options)
+}
@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use utils::vec3::Vec3;
 use utils::perlin::Perlin;
 
@@ -93,3 +94,42 @@ impl Texture for NoiseTexture {
         Box::new((*self).clone())
     }
 }
+
+/// 8-bit RGB image, rows stored top to bottom. `v` runs upwards and both coordinates wrap
+/// around, so textures repeat outside of `[0, 1]`. pixels are shared between clones.
+#[derive(Clone)]
+pub struct ImageTexture {
+    data: Arc<Vec<u8>>,
+    nx: usize,
+    ny: usize,
+}
+
+#[allow(dead_code)]
+impl ImageTexture {
+    pub fn new(pixels: Vec<u8>, nx: usize, ny: usize) -> Self {
+        assert_eq!(pixels.len(), 3 * nx * ny, "ImageTexture needs 3 bytes per pixel");
+        Self {
+            data: Arc::new(pixels),
+            nx: nx,
+            ny: ny,
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: Vec3) -> Vec3 {
+        if self.nx == 0 || self.ny == 0 {
+            return Vec3::new(0., 1., 1.);
+        }
+        let u = u - u.floor();
+        let v = v - v.floor();
+        let i = ((u * self.nx as f32) as usize).min(self.nx - 1);
+        let j = (((1. - v) * self.ny as f32) as usize).min(self.ny - 1);
+        let k = 3 * (i + self.nx * j);
+        Vec3::new(self.data[k] as f32 / 255., self.data[k + 1] as f32 / 255., self.data[k + 2] as f32 / 255.)
+    }
+
+    fn box_clone(&self) -> Box<Texture> {
+        Box::new((*self).clone())
+    }
+}
@@ -0,0 +1,219 @@
+use std::f32::consts::PI;
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::{unit_vector, Vec3};
+use utils::ray::Ray;
+use utils::material::Material;
+use utils::aabb::AABB;
+use utils::quadric::solve_quadratic;
+
+const MAX_BISECTIONS: usize = 100;
+
+fn eval(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().fold(0., |acc, &c| acc * x + c)
+}
+
+fn derivative(coeffs: &[f64]) -> Vec<f64> {
+    let n = coeffs.len() - 1;
+    coeffs[..n].iter().enumerate().map(|(i, &c)| c * (n - i) as f64).collect()
+}
+
+/// the single root of `coeffs` in `[lo, hi]`, on which the polynomial is monotonic and
+/// changes sign. newton steps that leave the bracket fall back to bisection.
+fn refine(coeffs: &[f64], slope: &[f64], mut lo: f64, mut hi: f64) -> f64 {
+    let rising = eval(coeffs, hi) > eval(coeffs, lo);
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..MAX_BISECTIONS {
+        let f = eval(coeffs, x);
+        if f == 0. {
+            return x;
+        }
+        if (f > 0.) == rising {
+            hi = x;
+        } else {
+            lo = x;
+        }
+        let df = eval(slope, x);
+        let newton = x - f / df;
+        let next = if df != 0. && newton > lo && newton < hi {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+        if (next - x).abs() <= 1e-12 * x.abs().max(1.) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+/// real roots of the polynomial `coeffs` (highest degree first) inside `[lo, hi]`, ascending.
+/// the roots of the derivative split the interval into monotonic pieces which hold at most
+/// one root each, so close roots are separated without relying on closed forms. a double
+/// root, i.e. a ray exactly tangent to the surface, is reported twice, which keeps the
+/// crossings of a closed surface paired.
+pub fn solve_polynomial(coeffs: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let first = coeffs.iter().position(|&c| c != 0.).unwrap_or(coeffs.len());
+    let coeffs = &coeffs[first..];
+    match coeffs.len() {
+        0 | 1 => return vec![],
+        2 => {
+            let x = -coeffs[1] / coeffs[0];
+            return if x >= lo && x <= hi { vec![x] } else { vec![] };
+        }
+        3 => {
+            return solve_quadratic(coeffs[0], coeffs[1], coeffs[2])
+                .into_iter()
+                .filter(|&x| x >= lo && x <= hi)
+                .collect();
+        }
+        _ => {}
+    }
+    let slope = derivative(coeffs);
+    let mut bounds = vec![lo];
+    bounds.extend(solve_polynomial(&slope, lo, hi));
+    bounds.push(hi);
+    let mut roots = vec![];
+    for w in bounds.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let (fa, fb) = (eval(coeffs, a), eval(coeffs, b));
+        let root = if fa == 0. {
+            Some(a)
+        } else if fb == 0. {
+            Some(b)
+        } else if (fa < 0.) != (fb < 0.) {
+            Some(refine(coeffs, &slope, a, b))
+        } else {
+            None
+        };
+        // a root on an inner boundary is a root of the derivative as well, so it is a
+        // double root and both of its neighbouring pieces report it
+        roots.extend(root);
+    }
+    roots
+}
+
+/// torus around the y axis through `center`. the tube of radius `minor_radius` follows a
+/// circle of radius `major_radius` in the xz plane. `u` runs around the y axis from +x
+/// towards +z, `v` around the tube starting at its outer equator.
+#[derive(Clone)]
+pub struct Torus {
+    center: Vec3,
+    major_radius: f32,
+    minor_radius: f32,
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl Torus {
+    pub fn new(center: Vec3, major_radius: f32, minor_radius: f32, m: Box<Material>) -> Self {
+        Self {
+            center: center,
+            major_radius: major_radius,
+            minor_radius: minor_radius,
+            mat: m,
+        }
+    }
+
+    /// ray parameters of every crossing with the surface inside `(t_min, t_max)`, ascending
+    fn roots(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<f32> {
+        let len = r.direction().len() as f64;
+        if len == 0. {
+            return vec![];
+        }
+        let o = r.origin().clone() - self.center.clone();
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let d = r.direction();
+        let (dx, dy, dz) = (d.x() as f64 / len, d.y() as f64 / len, d.z() as f64 / len);
+
+        // restrict the search to the bounding sphere and move the origin to where the ray
+        // enters it, which keeps the quartic well conditioned for distant rays
+        let (big, small) = (self.major_radius as f64, self.minor_radius as f64);
+        let bound = big + small;
+        let b = ox * dx + oy * dy + oz * dz;
+        let oo = ox * ox + oy * oy + oz * oz;
+        let (enter, exit) = match solve_quadratic(1., 2. * b, oo - bound * bound).as_slice() {
+            &[enter, exit] => (enter, exit),
+            _ => return vec![],
+        };
+        let lo = enter.max(t_min as f64 * len);
+        let hi = exit.min(t_max as f64 * len);
+        if lo > hi {
+            return vec![];
+        }
+        let (ox, oy, oz) = (ox + enter * dx, oy + enter * dy, oz + enter * dz);
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (p.x^2 + p.z^2) with p = o + t d and |d| = 1
+        let f = ox * dx + oy * dy + oz * dz;
+        let e = ox * ox + oy * oy + oz * oz + big * big - small * small;
+        let four_r2 = 4. * big * big;
+        let coeffs = [1.,
+                      4. * f,
+                      4. * f * f + 2. * e - four_r2 * (dx * dx + dz * dz),
+                      4. * e * f - 2. * four_r2 * (ox * dx + oz * dz),
+                      e * e - four_r2 * (ox * ox + oz * oz)];
+        solve_polynomial(&coeffs, lo - enter, hi - enter)
+            .into_iter()
+            .map(|t| ((t + enter) / len) as f32)
+            .filter(|&t| t > t_min && t < t_max)
+            .collect()
+    }
+
+    fn fill(&self, r: &Ray, t: f32, rec: &mut HitRecord) {
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        let local = rec.p.clone() - self.center.clone();
+        let ring = (local.x() * local.x() + local.z() * local.z()).sqrt();
+        let mut u = local.z().atan2(local.x()) / (2. * PI);
+        if u < 0. {
+            u += 1.;
+        }
+        let mut v = local.y().atan2(ring - self.major_radius) / (2. * PI);
+        if v < 0. {
+            v += 1.;
+        }
+        // from the nearest point of the tube's center circle out to the hit point
+        let axis = if ring > 0. {
+            Vec3::new(local.x(), 0., local.z()) * (self.major_radius / ring)
+        } else {
+            Vec3::new(self.major_radius, 0., 0.)
+        };
+        rec.u = u;
+        rec.v = v;
+        rec.normal = unit_vector(local - axis);
+        rec.mat = self.mat.clone();
+    }
+}
+
+impl Hitable for Torus {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        match self.roots(r, t_min, t_max).first() {
+            Some(&t) => {
+                self.fill(r, t, rec);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        self.roots(r, t_min, t_max)
+            .into_iter()
+            .map(|t| {
+                let mut rec = HitRecord::new(self.mat.clone());
+                self.fill(r, t, &mut rec);
+                rec
+            })
+            .collect()
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        let outer = self.major_radius + self.minor_radius;
+        let half = Vec3::new(outer, self.minor_radius, outer);
+        *vox = AABB::new(self.center.clone() - half.clone(), self.center.clone() + half);
+        true
+    }
+}
@@ -0,0 +1,111 @@
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::{cross, dot, unit_vector, Vec3};
+use utils::ray::Ray;
+use utils::material::Material;
+use utils::aabb::AABB;
+
+#[derive(Clone)]
+pub struct Triangle {
+    v: [Vec3; 3],
+    n: Option<[Vec3; 3]>,
+    uv: [(f32, f32); 3],
+    mat: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl Triangle {
+    /// flat shaded triangle, (u,v) run from (0,0) at `v0` to (1,0) at `v1` and (0,1) at `v2`
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, m: Box<Material>) -> Self {
+        Self {
+            v: [v0, v1, v2],
+            n: None,
+            uv: [(0., 0.), (1., 0.), (0., 1.)],
+            mat: m,
+        }
+    }
+
+    /// triangle with per-vertex shading normals and texture coordinates
+    pub fn with_attributes(v: [Vec3; 3], n: Option<[Vec3; 3]>, uv: [(f32, f32); 3], m: Box<Material>) -> Self {
+        Self {
+            v: v,
+            n: n,
+            uv: uv,
+            mat: m,
+        }
+    }
+}
+
+/// Möller–Trumbore ray/triangle test. returns `t` and the barycentric weights of `v1` and `v2`.
+pub fn ray_triangle(v0: &Vec3, v1: &Vec3, v2: &Vec3, r: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let e1 = v1.clone() - v0.clone();
+    let e2 = v2.clone() - v0.clone();
+    let pvec = cross(r.direction(), &e2);
+    let det = dot(&e1, &pvec);
+    // no fixed epsilon, it would scale with the triangle and drop tiny ones. rays parallel to
+    // the plane give a zero or denormal `det`, and the NaN or infinite weights fail the checks below
+    if det == 0. || !det.is_finite() {
+        return None;
+    }
+    let inv_det = 1. / det;
+    let tvec = r.origin().clone() - v0.clone();
+    let b1 = dot(&tvec, &pvec) * inv_det;
+    if !(0. ..=1.).contains(&b1) {
+        return None;
+    }
+    let qvec = cross(&tvec, &e1);
+    let b2 = dot(r.direction(), &qvec) * inv_det;
+    if b2.is_nan() || b2 < 0. || b1 + b2 > 1. {
+        return None;
+    }
+    let t = dot(&e2, &qvec) * inv_det;
+    if t < t_max && t > t_min {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+/// bounds of a triangle, padded so that axis-aligned triangles do not get a flat box
+pub fn triangle_bounds(v0: &Vec3, v1: &Vec3, v2: &Vec3) -> AABB {
+    let mut min = v0.clone();
+    let mut max = v0.clone();
+    for p in [v1, v2].iter() {
+        for a in 0..3 {
+            min.e[a] = min.e[a].min(p.e[a]);
+            max.e[a] = max.e[a].max(p.e[a]);
+        }
+    }
+    let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+    AABB::new(min - pad.clone(), max + pad)
+}
+
+impl Hitable for Triangle {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let (t, b1, b2) = match ray_triangle(&self.v[0], &self.v[1], &self.v[2], r, t_min, t_max) {
+            Some(h) => h,
+            None => return false,
+        };
+        let b0 = 1. - b1 - b2;
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.u = b0 * self.uv[0].0 + b1 * self.uv[1].0 + b2 * self.uv[2].0;
+        rec.v = b0 * self.uv[0].1 + b1 * self.uv[1].1 + b2 * self.uv[2].1;
+        rec.normal = match self.n {
+            Some(ref n) => unit_vector(n[0].clone() * b0 + n[1].clone() * b1 + n[2].clone() * b2),
+            None => {
+                unit_vector(cross(&(self.v[1].clone() - self.v[0].clone()),
+                                  &(self.v[2].clone() - self.v[0].clone())))
+            }
+        };
+        rec.mat = self.mat.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, vox: &mut AABB) -> bool {
+        *vox = triangle_bounds(&self.v[0], &self.v[1], &self.v[2]);
+        true
+    }
+}
@@ -0,0 +1,275 @@
+use utils::hitable::{Hitable, HitRecord};
+use utils::vec3::Vec3;
+use utils::ray::Ray;
+use utils::material::{Isotropic, Material};
+use utils::texture::Texture;
+use utils::random::drand48;
+use utils::aabb::AABB;
+use utils::perlin::Perlin;
+
+#[derive(Clone)]
+pub struct ConstantMedium {
+    boundary: Box<Hitable>,
+    density: f32,
+    phase_function: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl ConstantMedium {
+    pub fn new(b: Box<Hitable>, d: f32, a: Box<Texture>) -> Self {
+        Self {
+            boundary: b,
+            density: d,
+            phase_function: Box::new(Isotropic::new(a)),
+        }
+    }
+}
+
+impl Hitable for ConstantMedium {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let mut rec1 = HitRecord::new(rec.mat.clone());
+        let mut rec2 = HitRecord::new(rec.mat.clone());
+        if !self.boundary.hit(r, -f32::MAX, f32::MAX, &mut rec1) {
+            return false;
+        }
+        if !self.boundary.hit(r, rec1.t + 0.0001, f32::MAX, &mut rec2) {
+            return false;
+        }
+        rec1.t = rec1.t.max(t_min);
+        rec2.t = rec2.t.min(t_max);
+        if rec1.t >= rec2.t {
+            return false;
+        }
+        rec1.t = rec1.t.max(0.);
+        let dir_len = r.direction().len();
+        let distance_inside_boundary = (rec2.t - rec1.t) * dir_len;
+        let hit_distance = -(1. / self.density) * drand48().ln();
+        if hit_distance < distance_inside_boundary {
+            rec.t = rec1.t + hit_distance / dir_len;
+            rec.p = r.point_at_parameter(rec.t);
+            // arbitrary, the phase function does not look at it
+            rec.normal = Vec3::new(1., 0., 0.);
+            rec.mat = self.phase_function.clone();
+            return true;
+        }
+        false
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
+        self.boundary.bounding_box(t0, t1, vox)
+    }
+}
+
+/// spatially varying extinction coefficient of a participating medium.
+/// `max_density` must bound `density` everywhere inside the medium.
+pub trait Density {
+    fn density(&self, p: &Vec3) -> f32;
+    fn max_density(&self) -> f32;
+    fn box_clone(&self) -> Box<Density>;
+}
+
+impl Clone for Box<Density> {
+    fn clone(&self) -> Box<Density> {
+        self.box_clone()
+    }
+}
+
+#[derive(Clone)]
+pub struct PerlinDensity {
+    noise: Perlin,
+    scale: f32,
+    depth: i32,
+    max: f32,
+}
+
+#[allow(dead_code)]
+impl PerlinDensity {
+    pub fn new(sc: f32, depth: i32, max: f32) -> Self {
+        Self {
+            noise: Perlin::new(),
+            scale: sc,
+            depth: depth,
+            max: max,
+        }
+    }
+}
+
+impl Density for PerlinDensity {
+    fn density(&self, p: &Vec3) -> f32 {
+        self.max * self.noise.turb(p.clone() * self.scale, self.depth).min(1.)
+    }
+    fn max_density(&self) -> f32 {
+        self.max
+    }
+    fn box_clone(&self) -> Box<Density> {
+        Box::new((*self).clone())
+    }
+}
+
+/// densities on a regular `nx * ny * nz` lattice spanning `[min, max]`, trilinearly interpolated.
+#[derive(Clone)]
+pub struct GridDensity {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f32>,
+    min: Vec3,
+    max: Vec3,
+    majorant: f32,
+}
+
+#[allow(dead_code)]
+impl GridDensity {
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f32>, min: Vec3, max: Vec3) -> Self {
+        assert!(nx > 0 && ny > 0 && nz > 0 && data.len() == nx * ny * nz);
+        let majorant = data.iter().fold(0f32, |m, &d| m.max(d));
+        Self {
+            nx: nx,
+            ny: ny,
+            nz: nz,
+            data: data,
+            min: min,
+            max: max,
+            majorant: majorant,
+        }
+    }
+
+    fn at(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.data[(z * self.ny + y) * self.nx + x]
+    }
+}
+
+impl Density for GridDensity {
+    fn density(&self, p: &Vec3) -> f32 {
+        let n = [self.nx, self.ny, self.nz];
+        let mut i = [0usize; 3];
+        let mut f = [0f32; 3];
+        for a in 0..3 {
+            let g = (p.e[a] - self.min.e[a]) / (self.max.e[a] - self.min.e[a]) * (n[a] - 1) as f32;
+            if g.is_nan() || g < 0. || g > (n[a] - 1) as f32 {
+                return 0.;
+            }
+            i[a] = (g.floor() as usize).min(n[a].saturating_sub(2));
+            f[a] = g - i[a] as f32;
+        }
+        let mut accum = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let x = (i[0] + di).min(self.nx - 1);
+                    let y = (i[1] + dj).min(self.ny - 1);
+                    let z = (i[2] + dk).min(self.nz - 1);
+                    accum += (di as f32 * f[0] + (1 - di) as f32 * (1. - f[0])) *
+                             (dj as f32 * f[1] + (1 - dj) as f32 * (1. - f[1])) *
+                             (dk as f32 * f[2] + (1 - dk) as f32 * (1. - f[2])) * self.at(x, y, z);
+                }
+            }
+        }
+        accum
+    }
+    fn max_density(&self) -> f32 {
+        self.majorant
+    }
+    fn box_clone(&self) -> Box<Density> {
+        Box::new((*self).clone())
+    }
+}
+
+/// medium whose density varies inside `boundary`. free-flight distances are sampled
+/// with delta (Woodcock) tracking against the density's majorant, so the result is unbiased.
+#[derive(Clone)]
+pub struct HeterogeneousMedium {
+    boundary: Box<Hitable>,
+    density: Box<Density>,
+    phase_function: Box<Material>,
+}
+
+#[allow(dead_code)]
+impl HeterogeneousMedium {
+    pub fn new(b: Box<Hitable>, d: Box<Density>, a: Box<Texture>) -> Self {
+        Self {
+            boundary: b,
+            density: d,
+            phase_function: Box::new(Isotropic::new(a)),
+        }
+    }
+
+    fn entry_exit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let mut rec1 = HitRecord::new(self.phase_function.clone());
+        let mut rec2 = HitRecord::new(self.phase_function.clone());
+        if !self.boundary.hit(r, -f32::MAX, f32::MAX, &mut rec1) {
+            return None;
+        }
+        if !self.boundary.hit(r, rec1.t + 0.0001, f32::MAX, &mut rec2) {
+            return None;
+        }
+        let t0 = rec1.t.max(t_min).max(0.);
+        let t1 = rec2.t.min(t_max);
+        if t0 >= t1 {
+            return None;
+        }
+        Some((t0, t1))
+    }
+
+    /// unbiased estimate of the transmittance between `t_min` and `t_max` using ratio tracking.
+    pub fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        let majorant = self.density.max_density();
+        let (t0, t1) = match self.entry_exit(r, t_min, t_max) {
+            Some(range) => range,
+            None => return 1.,
+        };
+        if majorant <= 0. {
+            return 1.;
+        }
+        let dir_len = r.direction().len();
+        let mut tr = 1.;
+        let mut t = t0;
+        loop {
+            t -= (1. - drand48()).ln() / majorant / dir_len;
+            if t >= t1 {
+                return tr;
+            }
+            tr *= 1. - self.density.density(&r.point_at_parameter(t)) / majorant;
+        }
+    }
+}
+
+impl Hitable for HeterogeneousMedium {
+    fn box_clone(&self) -> Box<Hitable> {
+        Box::new((*self).clone())
+    }
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let majorant = self.density.max_density();
+        if majorant <= 0. {
+            return false;
+        }
+        let (t0, t1) = match self.entry_exit(r, t_min, t_max) {
+            Some(range) => range,
+            None => return false,
+        };
+        let dir_len = r.direction().len();
+        let mut t = t0;
+        loop {
+            t -= (1. - drand48()).ln() / majorant / dir_len;
+            if t >= t1 {
+                return false;
+            }
+            let p = r.point_at_parameter(t);
+            // real collision with probability density / majorant, null collision otherwise
+            if drand48() * majorant < self.density.density(&p) {
+                rec.t = t;
+                rec.p = p;
+                rec.normal = Vec3::new(1., 0., 0.);
+                rec.mat = self.phase_function.clone();
+                return true;
+            }
+        }
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, vox: &mut AABB) -> bool {
+        self.boundary.bounding_box(t0, t1, vox)
+    }
+}